@@ -0,0 +1,363 @@
+use std::{
+    fmt::{Display, Formatter},
+    fs,
+    io::{self, ErrorKind},
+    net::{IpAddr, SocketAddr},
+};
+
+use netstat2::{AddressFamilyFlags, ProtocolFlags, ProtocolSocketInfo, TcpState, get_sockets_info};
+use nu_protocol::{LabeledError, Record, Signals, Span, Value, record};
+use socket2::{Domain, SockAddr, Socket, Type};
+
+use crate::{
+    commands::list::{PortListConfig, collect_connections},
+    helpers::AsValue,
+};
+
+use super::CheckBindConfig;
+
+const UNPRIVILEGED_PORT_START: &str = "/proc/sys/net/ipv4/ip_unprivileged_port_start";
+
+/// Ports below this need privileges when the kernel does not say otherwise
+const DEFAULT_UNPRIVILEGED_PORT_START: u16 = 1024;
+
+/// How an existing socket overlaps the requested address
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Overlap {
+    /// Both are bound to the same address
+    SameAddress,
+    /// One of them is bound to the wildcard address of the family
+    Wildcard,
+    /// An ipv6 wildcard socket without `IPV6_V6ONLY` also covers ipv4
+    DualStack,
+}
+
+impl Overlap {
+    /// Overlap of the requested address with a socket bound to `existing`
+    fn between(requested: IpAddr, v6only: bool, existing: IpAddr) -> Option<Self> {
+        match (requested, existing) {
+            _ if requested == existing => Some(Overlap::SameAddress),
+            (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_))
+                if requested.is_unspecified() || existing.is_unspecified() =>
+            {
+                Some(Overlap::Wildcard)
+            }
+            (IpAddr::V6(v6), IpAddr::V4(_)) if v6.is_unspecified() && !v6only => {
+                Some(Overlap::DualStack)
+            }
+            // whether the existing socket set IPV6_V6ONLY is not visible
+            (IpAddr::V4(_), IpAddr::V6(v6)) if v6.is_unspecified() => Some(Overlap::DualStack),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Overlap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Overlap::SameAddress => "same_address",
+            Overlap::Wildcard => "wildcard_overlap",
+            Overlap::DualStack => "dual_stack",
+        })
+    }
+}
+
+impl AsValue for Overlap {
+    fn as_value(self, span: Span) -> Value {
+        self.to_string().as_value(span)
+    }
+}
+
+/// Attempts the bind and, when it fails, explains why
+pub(super) fn check_bind(
+    cfg: CheckBindConfig,
+    signals: &Signals,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let outcome = bind(&cfg);
+    let (causes, conflicts) = match &outcome {
+        Ok(()) => (vec![], vec![]),
+        Err(err) => diagnose(&cfg, err, signals, span)?,
+    };
+    let err = outcome.err();
+    Ok(record! {
+        "address" => cfg.address().to_string().as_value(span),
+        "port" => cfg.port().as_value(span),
+        "protocol" => protocol(&cfg).as_value(span),
+        "can_bind" => err.is_none().as_value(span),
+        "errno" => err.as_ref().and_then(io::Error::raw_os_error).map(i64::from).as_value(span),
+        "error" => err.as_ref().map(io::Error::to_string).as_value(span),
+        "causes" => causes.as_value(span),
+        "conflicts" => conflicts.as_value(span),
+    }
+    .as_value(span))
+}
+
+/// Causes of a failed bind, and the sockets in the way
+fn diagnose(
+    cfg: &CheckBindConfig,
+    err: &io::Error,
+    signals: &Signals,
+    span: Span,
+) -> Result<(Vec<Record>, Vec<Record>), LabeledError> {
+    let mut causes = vec![];
+    let mut conflicts = vec![];
+    match err.kind() {
+        ErrorKind::AddrInUse => {
+            let mut overlaps = vec![];
+            for (overlap, mut row) in conflicting_sockets(cfg, signals, span)? {
+                if !overlaps.contains(&overlap) {
+                    overlaps.push(overlap);
+                    causes.push(cause(overlap.to_string(), overlap_detail(cfg, overlap)));
+                }
+                row.insert("overlap", overlap.as_value(span));
+                conflicts.push(row);
+            }
+            let time_wait = time_wait_sockets(cfg);
+            if time_wait > 0 && !cfg.reuseaddr() {
+                causes.push(cause(
+                    "time_wait",
+                    format!(
+                        "{} closed connection(s) on this port are in TIME_WAIT, `--reuseaddr` (SO_REUSEADDR) binds over them",
+                        time_wait
+                    ),
+                ));
+            }
+            if causes.is_empty() {
+                causes.push(cause(
+                    "hidden_socket",
+                    "the socket holding the port is not visible, it may belong to another user or network namespace",
+                ));
+            }
+        }
+        ErrorKind::PermissionDenied if *cfg.port() < unprivileged_port_start() => {
+            causes.push(cause(
+                "privileged_port",
+                format!(
+                    "ports below {} need root or CAP_NET_BIND_SERVICE (net.ipv4.ip_unprivileged_port_start)",
+                    unprivileged_port_start()
+                ),
+            ));
+        }
+        ErrorKind::AddrNotAvailable => {
+            causes.push(cause(
+                "address_not_available",
+                "the address is not assigned to a local interface",
+            ));
+        }
+        _ => {}
+    }
+    Ok((causes, conflicts))
+}
+
+fn protocol(cfg: &CheckBindConfig) -> &'static str {
+    match cfg.udp() {
+        true => "udp",
+        false => "tcp",
+    }
+}
+
+/// Binds (and for tcp listens on) a socket set up as requested, closed right away
+fn bind(cfg: &CheckBindConfig) -> io::Result<()> {
+    let address = SocketAddr::new(*cfg.address(), *cfg.port());
+    let r#type = match cfg.udp() {
+        true => Type::DGRAM,
+        false => Type::STREAM,
+    };
+    let socket = Socket::new(Domain::for_address(address), r#type, None)?;
+    if address.is_ipv6() {
+        socket.set_only_v6(*cfg.v6only())?;
+    }
+    if *cfg.reuseaddr() {
+        socket.set_reuse_address(true)?;
+    }
+    if *cfg.reuseport() {
+        set_reuse_port(&socket)?;
+    }
+    socket.bind(&SockAddr::from(address))?;
+    if !cfg.udp() {
+        socket.listen(1)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+))]
+fn set_reuse_port(socket: &Socket) -> io::Result<()> {
+    socket.set_reuse_port(true)
+}
+
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+)))]
+fn set_reuse_port(_socket: &Socket) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "SO_REUSEPORT is not supported on this platform",
+    ))
+}
+
+/// Sockets of other processes on the port that overlap the requested address, as `port list -p` rows
+fn conflicting_sockets(
+    cfg: &CheckBindConfig,
+    signals: &Signals,
+    span: Span,
+) -> Result<Vec<(Overlap, Record)>, LabeledError> {
+    let overlap = |local_address: &str| {
+        local_address
+            .parse()
+            .ok()
+            .and_then(|existing| Overlap::between(*cfg.address(), *cfg.v6only(), existing))
+    };
+    let connections = collect_connections(PortListConfig::all(), signals, span, |c| {
+        c.local_port == *cfg.port()
+            && c.r#type == protocol(cfg)
+            && overlap(&c.local_address).is_some()
+    })?;
+    Ok(connections
+        .into_iter()
+        .filter_map(|c| Some((overlap(&c.local_address)?, Record::from(c))))
+        .collect())
+}
+
+/// Tcp sockets of the port waiting out TIME_WAIT, they belong to no process
+fn time_wait_sockets(cfg: &CheckBindConfig) -> usize {
+    if *cfg.udp() {
+        return 0;
+    }
+    let families = AddressFamilyFlags::IPV4 | AddressFamilyFlags::IPV6;
+    let Ok(sockets) = get_sockets_info(families, ProtocolFlags::TCP) else {
+        return 0;
+    };
+    sockets
+        .iter()
+        .filter(|socket| match &socket.protocol_socket_info {
+            ProtocolSocketInfo::Tcp(tcp) => {
+                tcp.local_port == *cfg.port()
+                    && tcp.state == TcpState::TimeWait
+                    && Overlap::between(*cfg.address(), *cfg.v6only(), tcp.local_addr).is_some()
+            }
+            _ => false,
+        })
+        .count()
+}
+
+fn unprivileged_port_start() -> u16 {
+    fs::read_to_string(UNPRIVILEGED_PORT_START)
+        .ok()
+        .and_then(|start| start.trim().parse().ok())
+        .unwrap_or(DEFAULT_UNPRIVILEGED_PORT_START)
+}
+
+fn overlap_detail(cfg: &CheckBindConfig, overlap: Overlap) -> String {
+    match overlap {
+        Overlap::SameAddress if *cfg.reuseport() => {
+            "a socket is bound to the same address without SO_REUSEPORT, or by another user".into()
+        }
+        Overlap::SameAddress => "a socket is already bound to the same address".into(),
+        Overlap::Wildcard if cfg.address().is_unspecified() => format!(
+            "{} covers every local address, including the one a socket already uses",
+            cfg.address()
+        ),
+        Overlap::Wildcard => {
+            "a socket is bound to the wildcard address, which covers this address".into()
+        }
+        Overlap::DualStack if cfg.address().is_ipv6() => {
+            ":: without IPV6_V6ONLY also covers ipv4, where a socket already uses the port; `--v6only` leaves ipv4 alone".into()
+        }
+        Overlap::DualStack => {
+            "a socket bound to :: without IPV6_V6ONLY also covers ipv4".into()
+        }
+    }
+}
+
+fn cause(cause: impl Into<String>, detail: impl Into<String>) -> Record {
+    let span = Span::unknown();
+    record! {
+        "cause" => cause.into().as_value(span),
+        "detail" => detail.into().as_value(span),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr, TcpListener};
+
+    use crate::commands::check_bind::check_bind_config::CheckBindConfigBuilder;
+
+    use super::*;
+
+    const V4: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const OTHER_V4: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    const ANY_V4: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+    const V6: IpAddr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+    const ANY_V6: IpAddr = IpAddr::V6(Ipv6Addr::UNSPECIFIED);
+
+    #[test]
+    fn overlap_between_addresses() {
+        let cases = [
+            (V4, false, V4, Some(Overlap::SameAddress)),
+            (ANY_V6, true, ANY_V6, Some(Overlap::SameAddress)),
+            (V4, false, OTHER_V4, None),
+            (ANY_V4, false, V4, Some(Overlap::Wildcard)),
+            (V4, false, ANY_V4, Some(Overlap::Wildcard)),
+            (ANY_V6, false, V6, Some(Overlap::Wildcard)),
+            (V6, false, ANY_V6, Some(Overlap::Wildcard)),
+            (ANY_V4, false, V6, None),
+            (ANY_V6, false, V4, Some(Overlap::DualStack)),
+            (ANY_V6, false, ANY_V4, Some(Overlap::DualStack)),
+            (ANY_V6, true, V4, None),
+            (V6, false, V4, None),
+            (V4, false, ANY_V6, Some(Overlap::DualStack)),
+            (ANY_V4, false, ANY_V6, Some(Overlap::DualStack)),
+            (V4, false, V6, None),
+        ];
+        for (requested, v6only, existing, expected) in cases {
+            assert_eq!(
+                Overlap::between(requested, v6only, existing),
+                expected,
+                "{requested} (v6only: {v6only}) against {existing}"
+            );
+        }
+    }
+
+    #[test]
+    fn bound_port_is_reported_in_use() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let cfg = CheckBindConfigBuilder::default()
+            .port(listener.local_addr().unwrap().port())
+            .address(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .udp(false)
+            .reuseaddr(false)
+            .reuseport(false)
+            .v6only(false)
+            .build()
+            .unwrap();
+        let span = Span::test_data();
+        let result = check_bind(cfg, &Signals::empty(), span).unwrap();
+        let result = result.as_record().unwrap();
+
+        assert_eq!(result.get("can_bind"), Some(&Value::test_bool(false)));
+        #[cfg(unix)]
+        assert_eq!(
+            result.get("errno"),
+            Some(&Value::test_int(i64::from(libc::EADDRINUSE)))
+        );
+        let causes = result.get("causes").unwrap().as_list().unwrap();
+        assert!(
+            causes
+                .iter()
+                .any(|cause| cause.get_data_by_key("cause")
+                    == Some(Value::test_string("same_address"))),
+            "{causes:?}"
+        );
+        let conflicts = result.get("conflicts").unwrap().as_list().unwrap();
+        assert!(conflicts.iter().any(|conflict| {
+            conflict.get_data_by_key("pid") == Some(Value::test_int(i64::from(std::process::id())))
+                && conflict.get_data_by_key("overlap") == Some(Value::test_string("same_address"))
+        }));
+    }
+}
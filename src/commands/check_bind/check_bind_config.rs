@@ -0,0 +1,65 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    str::FromStr,
+};
+
+use derive_builder::Builder;
+use derive_getters::Getters;
+use nu_plugin::EvaluatedCall;
+use nu_protocol::LabeledError;
+
+use crate::helpers::FlagHelper;
+
+#[derive(Clone, Builder, Debug, Getters)]
+pub struct CheckBindConfig {
+    port: u16,
+    /// Local address the bind is attempted on
+    address: IpAddr,
+    /// Bind a udp socket instead of a tcp listener
+    udp: bool,
+    /// Set `SO_REUSEADDR` before binding
+    reuseaddr: bool,
+    /// Set `SO_REUSEPORT` before binding
+    reuseport: bool,
+    /// Set `IPV6_V6ONLY` before binding, only for ipv6 addresses
+    v6only: bool,
+}
+
+impl TryFrom<&EvaluatedCall> for CheckBindConfig {
+    type Error = LabeledError;
+
+    fn try_from(call: &EvaluatedCall) -> Result<Self, Self::Error> {
+        let mut builder = CheckBindConfigBuilder::create_empty();
+        let port = call.req::<i64>(0)?;
+        builder.port(u16::try_from(port).map_err(|_| {
+            LabeledError::new("invalid port")
+                .with_label("not a valid port number", call.positional[0].span())
+        })?);
+        let address = match call.get_flag::<String>("address")? {
+            Some(address) => IpAddr::from_str(&address).map_err(|e| {
+                LabeledError::new(format!("invalid address: {}", e)).with_label(
+                    "expected an IP address",
+                    call.get_flag_span("address").unwrap_or(call.head),
+                )
+            })?,
+            None => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        };
+        let v6only = call.has_flag_or("v6only", false);
+        if v6only && address.is_ipv4() {
+            return Err(
+                LabeledError::new("`--v6only` needs an ipv6 address").with_label(
+                    "only applies to ipv6 sockets",
+                    call.get_flag_span("v6only").unwrap_or(call.head),
+                ),
+            );
+        }
+        builder.address(address);
+        builder.udp(call.has_flag_or("udp", false));
+        builder.reuseaddr(call.has_flag_or("reuseaddr", false));
+        builder.reuseport(call.has_flag_or("reuseport", false));
+        builder.v6only(v6only);
+        builder
+            .build()
+            .map_err(|e| LabeledError::new(e.to_string()))
+    }
+}
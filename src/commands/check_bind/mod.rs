@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod check_bind;
+mod check_bind_config;
+mod plugin;
+pub(super) use check_bind_config::CheckBindConfig;
+pub(crate) use plugin::PortCheckBind;
@@ -0,0 +1,89 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, SyntaxShape};
+
+use crate::PortExtension;
+
+use super::{CheckBindConfig, check_bind::check_bind};
+
+#[derive(Default)]
+pub struct PortCheckBind;
+
+impl PortCheckBind {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PluginCommand for PortCheckBind {
+    type Plugin = PortExtension;
+
+    fn name(&self) -> &str {
+        "port check-bind"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("port check-bind")
+            .required("port", SyntaxShape::Int, "local port to bind")
+            .named(
+                "address",
+                SyntaxShape::String,
+                "local address to bind (default: 0.0.0.0)",
+                Some('a'),
+            )
+            .switch(
+                "udp",
+                "bind a udp socket instead of a tcp listener",
+                Some('u'),
+            )
+            .switch(
+                "reuseaddr",
+                "set SO_REUSEADDR before binding, as most servers do",
+                None,
+            )
+            .switch("reuseport", "set SO_REUSEPORT before binding", None)
+            .switch(
+                "v6only",
+                "set IPV6_V6ONLY before binding an ipv6 address",
+                None,
+            )
+            .category(Category::Network)
+    }
+
+    fn description(&self) -> &str {
+        "Attempts to bind a local port and explains why the bind fails"
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "port check-bind 8080",
+                description: "check whether a server can listen on port 8080 of every ipv4 address",
+                result: None,
+            },
+            Example {
+                example: "port check-bind 8080 --address :: --v6only",
+                description: "check port 8080 on every ipv6 address, leaving ipv4 alone",
+                result: None,
+            },
+            Example {
+                example: "port check-bind 80 | get causes",
+                description: "why port 80 cannot be bound",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let cfg = CheckBindConfig::try_from(call)?;
+        Ok(PipelineData::Value(
+            check_bind(cfg, engine.signals(), call.head)?,
+            None,
+        ))
+    }
+}
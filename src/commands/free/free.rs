@@ -0,0 +1,225 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, ErrorKind},
+    net::{SocketAddr, TcpListener, UdpSocket},
+};
+
+use nu_protocol::{LabeledError, Signals, Span, Value};
+use rand::seq::SliceRandom;
+
+use crate::helpers::AsValue;
+
+use super::FreeConfig;
+
+const SERVICES_PATH: &str = "/etc/services";
+
+/// Ports the OS is asked for before giving up when `--avoid-known` keeps rejecting them
+const OS_PICK_ATTEMPTS: usize = 1000;
+
+/// Socket kept bound until every port is found, so none is handed out twice
+enum Held {
+    Tcp(TcpListener),
+    Udp(UdpSocket),
+}
+
+impl Held {
+    fn bind(address: SocketAddr, udp: bool) -> io::Result<Self> {
+        match udp {
+            true => UdpSocket::bind(address).map(Held::Udp),
+            false => TcpListener::bind(address).map(Held::Tcp),
+        }
+    }
+
+    fn port(&self) -> io::Result<u16> {
+        match self {
+            Held::Tcp(listener) => listener.local_addr(),
+            Held::Udp(socket) => socket.local_addr(),
+        }
+        .map(|address| address.port())
+    }
+}
+
+/// Finds ports that can be bound right now, the check sockets are closed before returning
+pub(super) fn free(cfg: FreeConfig, signals: &Signals, span: Span) -> Result<Value, LabeledError> {
+    let known = match cfg.avoid_known() {
+        true => known_ports(*cfg.udp()).map_err(|e| {
+            LabeledError::new(format!("cannot read {}: {}", SERVICES_PATH, e))
+                .with_label("needs the services file", span)
+        })?,
+        false => HashSet::new(),
+    };
+    let wanted = cfg.count().unwrap_or(1);
+    let mut held = vec![];
+    match cfg.candidates() {
+        Some(candidates) => {
+            let mut candidates = candidates.clone();
+            candidates.shuffle(&mut rand::rng());
+            for port in candidates.into_iter().filter(|port| !known.contains(port)) {
+                if held.len() == wanted {
+                    break;
+                }
+                signals.check(&span)?;
+                if let Some(socket) = try_bind(&cfg, port)? {
+                    held.push((port, socket));
+                }
+            }
+        }
+        None => {
+            // rejected ports stay bound as well, so the OS does not offer them again
+            let mut rejected = vec![];
+            for _ in 0..OS_PICK_ATTEMPTS {
+                if held.len() == wanted {
+                    break;
+                }
+                signals.check(&span)?;
+                let Some(socket) = try_bind(&cfg, 0)? else {
+                    continue;
+                };
+                let port = socket.port().map_err(|e| bind_error(&cfg, e))?;
+                match known.contains(&port) {
+                    true => rejected.push(socket),
+                    false => held.push((port, socket)),
+                }
+            }
+        }
+    }
+    if held.len() < wanted {
+        return Err(LabeledError::new("not enough free ports").with_label(
+            format!("found {} of the {} ports requested", held.len(), wanted),
+            *cfg.range_span(),
+        ));
+    }
+    let ports = held.into_iter().map(|(port, _)| port).collect::<Vec<_>>();
+    Ok(match cfg.count() {
+        Some(_) => ports.as_value(span),
+        None => ports[0].as_value(span),
+    })
+}
+
+/// Binds the port, `None` when it is taken or reserved for privileged processes
+fn try_bind(cfg: &FreeConfig, port: u16) -> Result<Option<Held>, LabeledError> {
+    match Held::bind(SocketAddr::new(*cfg.address(), port), *cfg.udp()) {
+        Ok(socket) => Ok(Some(socket)),
+        Err(err)
+            if matches!(
+                err.kind(),
+                ErrorKind::AddrInUse | ErrorKind::PermissionDenied
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(bind_error(cfg, err)),
+    }
+}
+
+fn bind_error(cfg: &FreeConfig, err: io::Error) -> LabeledError {
+    LabeledError::new(format!("cannot bind a port on {}: {}", cfg.address(), err)).with_label(
+        "is this address assigned to a local interface?",
+        *cfg.address_span(),
+    )
+}
+
+/// Ports `/etc/services` assigns to the protocol
+fn known_ports(udp: bool) -> io::Result<HashSet<u16>> {
+    Ok(parse_services(&fs::read_to_string(SERVICES_PATH)?, udp))
+}
+
+/// Ports a services file in the `/etc/services` format assigns to the protocol
+fn parse_services(services: &str, udp: bool) -> HashSet<u16> {
+    let protocol = match udp {
+        true => "udp",
+        false => "tcp",
+    };
+    services
+        .lines()
+        .filter_map(|line| line.split('#').next()?.split_whitespace().nth(1))
+        .filter_map(|entry| entry.split_once('/'))
+        .filter(|(_, entry_protocol)| *entry_protocol == protocol)
+        .filter_map(|(port, _)| port.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::commands::free::free_config::FreeConfigBuilder;
+
+    use super::*;
+
+    const SERVICES: &str = "\
+# Network services, Internet style
+tcpmux          1/tcp                           # TCP port service multiplexer
+ssh             22/tcp                          # SSH Remote Login Protocol
+domain          53/tcp
+domain          53/udp
+ntp             123/udp                         # Network Time Protocol
+   http   80/tcp    www     # WorldWideWeb HTTP
+#https          443/tcp
+broken          no-port/tcp
+bad             99999/tcp
+";
+
+    fn config(candidates: Option<Vec<u16>>, count: Option<usize>) -> FreeConfig {
+        FreeConfigBuilder::default()
+            .udp(false)
+            .candidates(candidates)
+            .range_span(Span::test_data())
+            .count(count)
+            .address(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .address_span(Span::test_data())
+            .avoid_known(false)
+            .build()
+            .unwrap()
+    }
+
+    fn ports(value: Value) -> Vec<u16> {
+        value
+            .as_list()
+            .unwrap()
+            .iter()
+            .map(|port| port.as_int().unwrap() as u16)
+            .collect()
+    }
+
+    #[test]
+    fn services_are_parsed_per_protocol() {
+        assert_eq!(
+            parse_services(SERVICES, false),
+            HashSet::from([1, 22, 53, 80])
+        );
+        assert_eq!(parse_services(SERVICES, true), HashSet::from([53, 123]));
+        assert!(parse_services("", false).is_empty());
+    }
+
+    #[test]
+    fn count_returns_distinct_free_ports() {
+        let ports =
+            ports(free(config(None, Some(5)), &Signals::empty(), Span::test_data()).unwrap());
+        assert_eq!(ports.len(), 5);
+        assert_eq!(ports.iter().collect::<HashSet<_>>().len(), 5);
+        // the ports were released when free returned
+        for port in ports {
+            TcpListener::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+        }
+    }
+
+    #[test]
+    fn bound_ports_are_not_returned() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let taken_port = taken.local_addr().unwrap().port();
+        let free_port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let cfg = config(Some(vec![taken_port, free_port]), Some(1));
+        let ports = ports(free(cfg, &Signals::empty(), Span::test_data()).unwrap());
+        assert_eq!(ports, vec![free_port]);
+
+        let cfg = config(Some(vec![taken_port, free_port]), Some(2));
+        let err = free(cfg, &Signals::empty(), Span::test_data()).unwrap_err();
+        assert_eq!(err.msg, "not enough free ports");
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    str::FromStr,
+};
+
+use derive_builder::Builder;
+use derive_getters::Getters;
+use nu_plugin::EvaluatedCall;
+use nu_protocol::{LabeledError, Range, Span, Value};
+
+use crate::helpers::FlagHelper;
+
+#[derive(Clone, Builder, Debug, Getters)]
+pub struct FreeConfig {
+    /// Look for a free udp port instead of tcp
+    udp: bool,
+    /// Ports picked from, in random order; `None` lets the OS choose
+    candidates: Option<Vec<u16>>,
+    range_span: Span,
+    /// Number of distinct ports wanted, `None` returns a single port instead of a list
+    count: Option<usize>,
+    /// Address the ports are bound to when checking them
+    address: IpAddr,
+    address_span: Span,
+    /// Skip ports listed in `/etc/services`
+    avoid_known: bool,
+}
+
+impl TryFrom<&EvaluatedCall> for FreeConfig {
+    type Error = LabeledError;
+
+    fn try_from(call: &EvaluatedCall) -> Result<Self, Self::Error> {
+        let mut builder = FreeConfigBuilder::create_empty();
+        builder.udp(call.has_flag_or("udp", false));
+        builder.candidates(match call.get_flag_value("range") {
+            Some(value) => Some(ports_in(&value)?),
+            None => None,
+        });
+        builder.range_span(call.get_flag_span("range").unwrap_or(call.head));
+        builder.count(match call.get_flag_value("count") {
+            Some(value) => match value.as_int()? {
+                count if count > 0 => Some(count as usize),
+                _ => {
+                    return Err(LabeledError::new("invalid count")
+                        .with_label("expected a positive number of ports", value.span()));
+                }
+            },
+            None => None,
+        });
+        builder.address(match call.get_flag::<String>("address")? {
+            Some(address) => IpAddr::from_str(&address).map_err(|e| {
+                LabeledError::new(format!("invalid address: {}", e)).with_label(
+                    "expected an IP address",
+                    call.get_flag_span("address").unwrap_or(call.head),
+                )
+            })?,
+            None => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        });
+        builder.address_span(call.get_flag_span("address").unwrap_or(call.head));
+        builder.avoid_known(call.has_flag_or("avoid-known", false));
+        builder
+            .build()
+            .map_err(|e| LabeledError::new(e.to_string()))
+    }
+}
+
+/// Valid ports of an int range such as `20000..30000`, port 0 excluded
+fn ports_in(value: &Value) -> Result<Vec<u16>, LabeledError> {
+    let invalid =
+        |label: &str| LabeledError::new("invalid port range").with_label(label, value.span());
+    let Range::IntRange(range) = value.as_range()? else {
+        return Err(invalid("expected a range of integers"));
+    };
+    let ports = (1..=u16::MAX)
+        .filter(|port| range.contains(*port as i64))
+        .collect::<Vec<_>>();
+    match ports.is_empty() {
+        true => Err(invalid("the range contains no port between 1 and 65535")),
+        false => Ok(ports),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{Range, ast::RangeInclusion};
+
+    use super::*;
+
+    fn range(start: i64, end: i64, inclusion: RangeInclusion) -> Value {
+        let span = Span::test_data();
+        Value::test_range(
+            Range::new(
+                Value::test_int(start),
+                Value::test_nothing(),
+                Value::test_int(end),
+                inclusion,
+                span,
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn ports_of_a_range() {
+        let ports = ports_in(&range(20000, 20003, RangeInclusion::RightExclusive)).unwrap();
+        assert_eq!(ports, vec![20000, 20001, 20002]);
+    }
+
+    #[test]
+    fn range_is_clipped_to_valid_ports() {
+        let ports = ports_in(&range(-10, 70000, RangeInclusion::Inclusive)).unwrap();
+        assert_eq!(ports.first(), Some(&1));
+        assert_eq!(ports.last(), Some(&u16::MAX));
+        assert_eq!(ports.len(), u16::MAX as usize);
+    }
+
+    #[test]
+    fn range_without_valid_ports_is_an_error() {
+        for value in [
+            range(70000, 80000, RangeInclusion::Inclusive),
+            range(0, 0, RangeInclusion::Inclusive),
+            range(100, 100, RangeInclusion::RightExclusive),
+        ] {
+            let err = ports_in(&value).unwrap_err();
+            assert_eq!(err.msg, "invalid port range");
+        }
+    }
+}
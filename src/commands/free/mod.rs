@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod free;
+mod free_config;
+mod plugin;
+pub(super) use free_config::FreeConfig;
+pub(crate) use plugin::PortFree;
@@ -0,0 +1,90 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, SyntaxShape};
+
+use crate::PortExtension;
+
+use super::{FreeConfig, free::free};
+
+#[derive(Default)]
+pub struct PortFree;
+
+impl PortFree {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PluginCommand for PortFree {
+    type Plugin = PortExtension;
+
+    fn name(&self) -> &str {
+        "port free"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("port free")
+            .switch("udp", "look for a free udp port instead of tcp", Some('u'))
+            .named(
+                "range",
+                SyntaxShape::Range,
+                "pick the ports from this range, e.g. 20000..30000 (default: chosen by the OS)",
+                Some('r'),
+            )
+            .named(
+                "count",
+                SyntaxShape::Int,
+                "return a list of this many distinct ports",
+                Some('c'),
+            )
+            .named(
+                "address",
+                SyntaxShape::String,
+                "local address the ports must be free on (default: 0.0.0.0)",
+                Some('a'),
+            )
+            .switch(
+                "avoid-known",
+                "skip the ports /etc/services assigns to a service",
+                Some('k'),
+            )
+            .category(Category::Network)
+    }
+
+    fn description(&self) -> &str {
+        "Returns a local port that is not in use, checked by binding it"
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "port free",
+                description: "a free tcp port chosen by the OS",
+                result: None,
+            },
+            Example {
+                example: "port free --range 20000..30000 --count 3",
+                description: "three distinct free tcp ports between 20000 and 30000",
+                result: None,
+            },
+            Example {
+                example: "port free --udp --address 127.0.0.1 --avoid-known",
+                description: "a udp port free on the loopback address and not assigned to a known service",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let cfg = FreeConfig::try_from(call)?;
+        Ok(PipelineData::Value(
+            free(cfg, engine.signals(), call.head)?,
+            None,
+        ))
+    }
+}
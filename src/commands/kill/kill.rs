@@ -0,0 +1,296 @@
+use std::{
+    collections::HashSet,
+    fmt::{Display, Formatter},
+    time::{Duration, Instant},
+};
+
+use nu_protocol::{LabeledError, Signals, Span, Value, record};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessStatus, ProcessesToUpdate, Signal, System};
+
+use crate::{
+    commands::list::{ConnectionInfo, PortListConfig, collect_connections, processes},
+    helpers::AsValue,
+};
+
+use super::KillConfig;
+
+/// How often processes are checked while waiting for them to exit
+const EXIT_POLL: Duration = Duration::from_millis(100);
+
+/// What happened to a target process
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    /// `--dry-run`, nothing was sent
+    DryRun,
+    /// Protected process left alone
+    Skipped,
+    /// The signal was delivered
+    Signaled,
+    /// The process exited within the grace period (`--force`)
+    Exited,
+    /// The process outlived the grace period and got SIGKILL (`--force`)
+    Killed,
+    Failed,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Outcome::DryRun => "dry_run",
+            Outcome::Skipped => "skipped",
+            Outcome::Signaled => "signaled",
+            Outcome::Exited => "exited",
+            Outcome::Killed => "killed",
+            Outcome::Failed => "failed",
+        })
+    }
+}
+
+impl AsValue for Outcome {
+    fn as_value(self, span: Span) -> Value {
+        self.to_string().as_value(span)
+    }
+}
+
+struct Target {
+    pid: u32,
+    process_name: Option<String>,
+    sockets: Vec<String>,
+    outcome: Outcome,
+    error: Option<String>,
+}
+
+impl Target {
+    fn fail(&mut self, error: String) {
+        self.outcome = Outcome::Failed;
+        self.error = Some(error);
+    }
+}
+
+/// Signals every process listening on the port, returns a record per process
+pub(super) fn kill(cfg: KillConfig, signals: &Signals, span: Span) -> Result<Value, LabeledError> {
+    let connections = collect_connections(PortListConfig::all(), signals, span, |c| {
+        listens_on(c, &cfg)
+    })?;
+    let mut targets = group(connections);
+    let protected = protected_pids();
+    let system = processes(ProcessesToUpdate::All, ProcessRefreshKind::nothing());
+    let (signal_name, signal) = *cfg.signal();
+    for target in &mut targets {
+        if !cfg.force_protected() && protected.contains(&target.pid) {
+            target.outcome = Outcome::Skipped;
+            target.error =
+                Some("protected process (pid 1 or nushell), use --force-protected".into());
+            continue;
+        }
+        if *cfg.dry_run() {
+            target.outcome = Outcome::DryRun;
+            continue;
+        }
+        match send(&system, target.pid, signal) {
+            Ok(()) => target.outcome = Outcome::Signaled,
+            Err(err) => target.fail(err),
+        }
+    }
+    if *cfg.force() {
+        escalate(&mut targets, cfg.grace(), signals, span)?;
+    }
+
+    let rows = targets.into_iter().map(|target| {
+        record! {
+            "pid" => target.pid.as_value(span),
+            "process_name" => target.process_name.as_value(span),
+            "sockets" => target.sockets.as_value(span),
+            "signal" => signal_name.as_value(span),
+            "outcome" => target.outcome.as_value(span),
+            "error" => target.error.as_value(span),
+        }
+        .as_value(span)
+    });
+    Ok(Value::list(rows.collect(), span))
+}
+
+fn listens_on(connection: &ConnectionInfo, cfg: &KillConfig) -> bool {
+    let protocol = match cfg.udp() {
+        true => "udp",
+        false => "tcp",
+    };
+    connection.local_port == *cfg.port()
+        && connection.r#type == protocol
+        && connection.state == "LISTEN"
+        && cfg
+            .address()
+            .is_none_or(|address| connection.local_address.parse().ok() == Some(address))
+}
+
+/// One target per process, with the local addresses it listens on
+fn group(connections: Vec<ConnectionInfo>) -> Vec<Target> {
+    let mut targets: Vec<Target> = vec![];
+    for connection in connections {
+        let socket = format!("{}:{}", connection.local_address, connection.local_port);
+        match targets
+            .iter_mut()
+            .find(|target| target.pid == connection.pid)
+        {
+            Some(target) => target.sockets.push(socket),
+            None => targets.push(Target {
+                pid: connection.pid,
+                process_name: connection.process_info.map(|process| process.process_name),
+                sockets: vec![socket],
+                outcome: Outcome::Signaled,
+                error: None,
+            }),
+        }
+    }
+    targets
+}
+
+/// Init, this plugin and the nushell process running it
+fn protected_pids() -> HashSet<u32> {
+    let own = std::process::id();
+    let system = processes(
+        ProcessesToUpdate::Some(&[Pid::from_u32(own)]),
+        ProcessRefreshKind::nothing(),
+    );
+    let mut pids = HashSet::from([0, 1, own]);
+    if let Some(parent) = system
+        .process(Pid::from_u32(own))
+        .and_then(|process| process.parent())
+    {
+        pids.insert(parent.as_u32());
+    }
+    pids
+}
+
+fn send(system: &System, pid: u32, signal: Signal) -> Result<(), String> {
+    let process = system
+        .process(Pid::from_u32(pid))
+        .ok_or("the process is gone")?;
+    match process.kill_with(signal) {
+        Some(true) => Ok(()),
+        Some(false) => Err("cannot send the signal (permission denied?)".into()),
+        None => Err(format!("{:?} is not supported on this platform", signal)),
+    }
+}
+
+/// Waits up to `grace` for the signaled processes to exit, then sends SIGKILL to the others
+fn escalate(
+    targets: &mut [Target],
+    grace: &Duration,
+    signals: &Signals,
+    span: Span,
+) -> Result<(), LabeledError> {
+    let deadline = Instant::now() + *grace;
+    loop {
+        let mut system = System::new();
+        let waiting = targets
+            .iter()
+            .filter(|target| target.outcome == Outcome::Signaled)
+            .map(|target| Pid::from_u32(target.pid))
+            .collect::<Vec<_>>();
+        system.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&waiting),
+            true,
+            ProcessRefreshKind::nothing(),
+        );
+        for target in targets
+            .iter_mut()
+            .filter(|target| target.outcome == Outcome::Signaled)
+        {
+            let alive = system
+                .process(Pid::from_u32(target.pid))
+                .is_some_and(|process| process.status() != ProcessStatus::Zombie);
+            if !alive {
+                target.outcome = Outcome::Exited;
+            } else if Instant::now() >= deadline {
+                match send(&system, target.pid, Signal::Kill) {
+                    Ok(()) => target.outcome = Outcome::Killed,
+                    Err(err) => target.fail(err),
+                }
+            }
+        }
+        if targets
+            .iter()
+            .all(|target| target.outcome != Outcome::Signaled)
+        {
+            return Ok(());
+        }
+        signals.check(&span).map_err(LabeledError::from)?;
+        std::thread::sleep(EXIT_POLL.min(deadline.saturating_duration_since(Instant::now())));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, TcpListener};
+
+    use crate::commands::kill::kill_config::KillConfigBuilder;
+
+    use super::*;
+
+    fn config(port: u16, udp: bool, address: Option<IpAddr>) -> KillConfig {
+        KillConfigBuilder::default()
+            .port(port)
+            .udp(udp)
+            .address(address)
+            .signal(("TERM", Signal::Term))
+            .force(false)
+            .grace(Duration::ZERO)
+            .force_protected(false)
+            .dry_run(true)
+            .build()
+            .unwrap()
+    }
+
+    fn listener(r#type: &str, address: &str, port: u16) -> ConnectionInfo {
+        ConnectionInfo {
+            r#type: r#type.into(),
+            local_address: address.into(),
+            local_port: port,
+            state: "LISTEN".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn listens_on_filters_by_protocol() {
+        let tcp = config(8080, false, None);
+        let udp = config(8080, true, None);
+        assert!(listens_on(&listener("tcp", "0.0.0.0", 8080), &tcp));
+        assert!(!listens_on(&listener("udp", "0.0.0.0", 8080), &tcp));
+        assert!(listens_on(&listener("udp", "0.0.0.0", 8080), &udp));
+        assert!(!listens_on(&listener("tcp", "0.0.0.0", 8081), &tcp));
+    }
+
+    #[test]
+    fn listens_on_filters_by_address() {
+        let cfg = config(8080, false, Some("::1".parse().unwrap()));
+        assert!(listens_on(&listener("tcp", "::1", 8080), &cfg));
+        assert!(!listens_on(&listener("tcp", "::", 8080), &cfg));
+        assert!(!listens_on(&listener("tcp", "127.0.0.1", 8080), &cfg));
+    }
+
+    #[test]
+    fn listens_on_ignores_connected_sockets() {
+        let mut established = listener("tcp", "127.0.0.1", 8080);
+        established.state = "ESTABLISHED".into();
+        assert!(!listens_on(&established, &config(8080, false, None)));
+    }
+
+    #[test]
+    fn own_process_is_skipped() {
+        // the test process listens itself, so it is protected like the plugin would be
+        let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let cfg = config(port, false, None);
+        let rows = kill(cfg, &Signals::empty(), Span::test_data()).unwrap();
+        let rows = rows.as_list().unwrap();
+        assert_eq!(rows.len(), 1);
+        let row = rows[0].as_record().unwrap();
+        assert_eq!(
+            row.get("pid"),
+            Some(&Value::test_int(std::process::id().into()))
+        );
+        assert_eq!(row.get("outcome"), Some(&Value::test_string("skipped")));
+    }
+}
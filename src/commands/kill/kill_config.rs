@@ -0,0 +1,150 @@
+use std::{net::IpAddr, str::FromStr, time::Duration};
+
+use derive_builder::Builder;
+use derive_getters::Getters;
+use nu_plugin::EvaluatedCall;
+use nu_protocol::{LabeledError, Value};
+use sysinfo::Signal;
+
+use crate::helpers::FlagHelper;
+
+const DEFAULT_GRACE: Duration = Duration::from_secs(5);
+
+/// Signals accepted by `--signal`, with their POSIX number when it is the same everywhere
+const SIGNALS: &[(&str, Option<i64>, Signal)] = &[
+    ("HUP", Some(1), Signal::Hangup),
+    ("INT", Some(2), Signal::Interrupt),
+    ("QUIT", Some(3), Signal::Quit),
+    ("KILL", Some(9), Signal::Kill),
+    ("TERM", Some(15), Signal::Term),
+    ("USR1", None, Signal::User1),
+    ("USR2", None, Signal::User2),
+    ("STOP", None, Signal::Stop),
+    ("CONT", None, Signal::Continue),
+];
+
+#[derive(Clone, Builder, Debug, Getters)]
+pub struct KillConfig {
+    port: u16,
+    /// Target udp sockets instead of tcp listeners
+    udp: bool,
+    /// Only sockets bound to this local address
+    address: Option<IpAddr>,
+    /// Name (without `SIG`) and value of the signal sent first
+    signal: (&'static str, Signal),
+    /// Send SIGKILL to processes still alive after `grace`
+    force: bool,
+    grace: Duration,
+    /// Also signal pid 1 and the nushell process
+    force_protected: bool,
+    /// Only report the processes that would be signaled
+    dry_run: bool,
+}
+
+impl TryFrom<&EvaluatedCall> for KillConfig {
+    type Error = LabeledError;
+
+    fn try_from(call: &EvaluatedCall) -> Result<Self, Self::Error> {
+        let mut builder = KillConfigBuilder::create_empty();
+        let port = call.req::<i64>(0)?;
+        builder.port(u16::try_from(port).map_err(|_| {
+            LabeledError::new("invalid port")
+                .with_label("not a valid port number", call.positional[0].span())
+        })?);
+        builder.udp(call.has_flag_or("udp", false));
+        builder.address(match call.get_flag::<String>("address")? {
+            Some(address) => Some(IpAddr::from_str(&address).map_err(|e| {
+                LabeledError::new(format!("invalid address: {}", e)).with_label(
+                    "expected an IP address",
+                    call.get_flag_span("address").unwrap_or(call.head),
+                )
+            })?),
+            None => None,
+        });
+        builder.signal(match call.get_flag_value("signal") {
+            Some(value) => signal_of(&value)?,
+            None => ("TERM", Signal::Term),
+        });
+        builder.force(call.has_flag_or("force", false));
+        builder.grace(match call.get_flag_value("grace") {
+            Some(value) => match value.as_duration()? {
+                nanos if nanos >= 0 => Duration::from_nanos(nanos as u64),
+                _ => {
+                    return Err(LabeledError::new("invalid grace period")
+                        .with_label("the grace period cannot be negative", value.span()));
+                }
+            },
+            None => DEFAULT_GRACE,
+        });
+        builder.force_protected(call.has_flag_or("force-protected", false));
+        builder.dry_run(call.has_flag_or("dry-run", false));
+        builder
+            .build()
+            .map_err(|e| LabeledError::new(e.to_string()))
+    }
+}
+
+/// Parses `TERM`, `SIGTERM`, `term` or `15`
+fn signal_of(value: &Value) -> Result<(&'static str, Signal), LabeledError> {
+    let found = match value {
+        Value::Int { val, .. } => SIGNALS.iter().find(|(_, number, _)| *number == Some(*val)),
+        _ => {
+            let name = value.coerce_str()?.to_uppercase();
+            let name = name.strip_prefix("SIG").unwrap_or(&name).to_string();
+            SIGNALS.iter().find(|(known, ..)| *known == name)
+        }
+    };
+    found
+        .map(|(name, _, signal)| (*name, *signal))
+        .ok_or_else(|| {
+            let names = SIGNALS
+                .iter()
+                .map(|(name, ..)| *name)
+                .collect::<Vec<_>>()
+                .join(", ");
+            LabeledError::new("unknown signal").with_label(
+                format!("expected one of: {} (or 1, 2, 3, 9, 15)", names),
+                value.span(),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name_of(value: Value) -> Result<&'static str, LabeledError> {
+        signal_of(&value).map(|(name, _)| name)
+    }
+
+    #[test]
+    fn signal_by_name() {
+        assert_eq!(name_of(Value::test_string("TERM")).unwrap(), "TERM");
+        assert_eq!(name_of(Value::test_string("USR1")).unwrap(), "USR1");
+    }
+
+    #[test]
+    fn signal_with_sig_prefix() {
+        assert_eq!(name_of(Value::test_string("SIGKILL")).unwrap(), "KILL");
+        assert_eq!(name_of(Value::test_string("sighup")).unwrap(), "HUP");
+    }
+
+    #[test]
+    fn signal_in_lowercase() {
+        assert_eq!(name_of(Value::test_string("int")).unwrap(), "INT");
+    }
+
+    #[test]
+    fn signal_by_number() {
+        assert_eq!(name_of(Value::test_int(9)).unwrap(), "KILL");
+        assert_eq!(name_of(Value::test_int(15)).unwrap(), "TERM");
+    }
+
+    #[test]
+    fn unknown_signals_are_rejected() {
+        assert!(name_of(Value::test_string("FOO")).is_err());
+        assert!(name_of(Value::test_string("SIG")).is_err());
+        // numbers that differ between platforms are only accepted by name
+        assert!(name_of(Value::test_int(10)).is_err());
+    }
+}
@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod kill;
+mod kill_config;
+mod plugin;
+pub(super) use kill_config::KillConfig;
+pub(crate) use plugin::PortKill;
@@ -0,0 +1,114 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, SyntaxShape};
+
+use crate::PortExtension;
+
+use super::{KillConfig, kill::kill};
+
+#[derive(Default)]
+pub struct PortKill;
+
+impl PortKill {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PluginCommand for PortKill {
+    type Plugin = PortExtension;
+
+    fn name(&self) -> &str {
+        "port kill"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("port kill")
+            .required(
+                "port",
+                SyntaxShape::Int,
+                "local port whose listening processes are signaled",
+            )
+            .switch(
+                "udp",
+                "target processes bound to the udp port instead of tcp listeners",
+                Some('u'),
+            )
+            .named(
+                "address",
+                SyntaxShape::String,
+                "only target sockets bound to this local address",
+                Some('a'),
+            )
+            .named(
+                "signal",
+                SyntaxShape::OneOf(vec![SyntaxShape::String, SyntaxShape::Int]),
+                "signal to send: TERM, INT, HUP, QUIT, KILL, USR1, USR2, STOP, CONT or 1, 2, 3, 9, 15 (default: TERM)",
+                Some('s'),
+            )
+            .switch(
+                "force",
+                "send SIGKILL to processes still running after the grace period",
+                Some('f'),
+            )
+            .named(
+                "grace",
+                SyntaxShape::Duration,
+                "time `--force` gives processes to exit (default: 5 Seconds)",
+                Some('g'),
+            )
+            .switch(
+                "force-protected",
+                "also signal pid 1 and the nushell process running this plugin",
+                None,
+            )
+            .switch(
+                "dry-run",
+                "only show the processes that would be signaled",
+                Some('n'),
+            )
+            .category(Category::Network)
+    }
+
+    fn description(&self) -> &str {
+        "Signals the processes listening on a local port (SIGTERM by default) and reports what happened to each"
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "port kill 3000 --dry-run",
+                description: "show which processes listen on port 3000 without touching them",
+                result: None,
+            },
+            Example {
+                example: "port kill 3000",
+                description: "ask whatever listens on port 3000 to terminate",
+                result: None,
+            },
+            Example {
+                example: "port kill 8080 --force --grace 2sec",
+                description: "terminate the listeners of port 8080, killing those still running after 2 seconds",
+                result: None,
+            },
+            Example {
+                example: "port kill 5353 --udp --address 0.0.0.0 --signal HUP",
+                description: "send SIGHUP to the process bound to udp port 5353 on all ipv4 interfaces",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let cfg = KillConfig::try_from(call)?;
+        Ok(PipelineData::Value(
+            kill(cfg, engine.signals(), call.head)?,
+            None,
+        ))
+    }
+}
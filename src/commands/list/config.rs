@@ -17,6 +17,18 @@ pub struct PortListConfig {
 }
 
 impl PortListConfig {
+    /// Every socket of both families and protocols, with process info
+    pub fn all() -> Self {
+        Self {
+            v4: true,
+            v6: true,
+            tcp: true,
+            udp: true,
+            listeners_only: false,
+            process_info: true,
+        }
+    }
+
     pub fn address_family_flags(self) -> AddressFamilyFlags {
         let mut flags = AddressFamilyFlags::empty();
         if self.v4 {
@@ -25,7 +37,7 @@ impl PortListConfig {
         if self.v6 {
             flags |= AddressFamilyFlags::IPV6;
         }
-        return flags;
+        flags
     }
     pub fn protocol_flags(self) -> ProtocolFlags {
         let mut flags = ProtocolFlags::empty();
@@ -35,7 +47,7 @@ impl PortListConfig {
         if self.udp {
             flags |= ProtocolFlags::UDP;
         }
-        return flags;
+        flags
     }
 }
 
@@ -50,8 +62,8 @@ impl TryFrom<&EvaluatedCall> for PortListConfig {
         builder.tcp(value.missing_flag_or("disable-tcp", true));
         builder.listeners_only(value.has_flag_or("listeners", false));
         builder.process_info(value.has_flag_or("process-info", false));
-        return builder
+        builder
             .build()
-            .map_err(|e| LabeledError::new(e.to_string()));
+            .map_err(|e| LabeledError::new(e.to_string()))
     }
 }
@@ -17,40 +17,23 @@ pub struct ConnectionInfo {
     pub pid: u32,
     pub process_info: Option<ProcessInfo>,
 }
-impl Into<Record> for ConnectionInfo {
-    fn into(self) -> Record {
+impl From<ConnectionInfo> for Record {
+    fn from(info: ConnectionInfo) -> Self {
         let span = Span::unknown();
         let result = &mut record! {
-          "pid"=>self.pid.as_value(span),
-          "type" => self.r#type().as_value(span),
-          "ip_version" => self.ip_version.as_value(span),
-          "local_address" =>self.local_address.as_value(span),
-          "local_port" => self.local_port.as_value(span),
-          "remote_address" => self.remote_address.as_value(span),
-          "remote_port" =>  self.remote_port.as_value(span),
-          "state" => self.state.as_value(span),
+          "pid"=>info.pid.as_value(span),
+          "type" => info.r#type().as_value(span),
+          "ip_version" => info.ip_version.as_value(span),
+          "local_address" =>info.local_address.as_value(span),
+          "local_port" => info.local_port.as_value(span),
+          "remote_address" => info.remote_address.as_value(span),
+          "remote_port" =>  info.remote_port.as_value(span),
+          "state" => info.state.as_value(span),
         };
-        if let Some(pf) = self.process_info {
-            result.insert("process_name", pf.process_name.as_value(span));
-            result.insert("cmd", pf.cmd.as_value(span));
-            result.insert("exe_path", pf.exe_path.as_value(span));
-            result.insert("process_status", pf.process_status.as_value(span));
-            result.insert("process_user", pf.process_user.as_value(span));
-            result.insert("process_group", pf.process_group.as_value(span));
-            result.insert(
-                "process_effective_user",
-                pf.process_effective_user.as_value(span),
-            );
-            result.insert(
-                "process_effective_group",
-                pf.process_effective_group.as_value(span),
-            );
-            result.insert(
-                "process_environments",
-                pf.process_environments.as_value(span),
-            );
+        if let Some(pf) = info.process_info {
+            pf.write_to(result, span);
         }
-        return result.to_owned();
+        result.to_owned()
     }
 }
 
@@ -67,6 +50,29 @@ pub struct ProcessInfo {
     pub process_environments: Vec<String>,
 }
 
+impl ProcessInfo {
+    pub fn write_to(self, record: &mut Record, span: Span) {
+        record.insert("process_name", self.process_name.as_value(span));
+        record.insert("cmd", self.cmd.as_value(span));
+        record.insert("exe_path", self.exe_path.as_value(span));
+        record.insert("process_status", self.process_status.as_value(span));
+        record.insert("process_user", self.process_user.as_value(span));
+        record.insert("process_group", self.process_group.as_value(span));
+        record.insert(
+            "process_effective_user",
+            self.process_effective_user.as_value(span),
+        );
+        record.insert(
+            "process_effective_group",
+            self.process_effective_group.as_value(span),
+        );
+        record.insert(
+            "process_environments",
+            self.process_environments.as_value(span),
+        );
+    }
+}
+
 impl From<&Process> for ProcessInfo {
     fn from(value: &Process) -> Self {
         ProcessInfo {
@@ -3,4 +3,6 @@ mod connection_info;
 mod plugin;
 mod port_list;
 pub(super) use config::PortListConfig;
+pub(crate) use connection_info::ConnectionInfo;
 pub(crate) use plugin::PortList;
+pub(crate) use port_list::{collect_connections, processes};
@@ -62,12 +62,12 @@ impl PluginCommand for PortList {
     fn run(
         &self,
         _plugin: &Self::Plugin,
-        _engine: &EngineInterface,
+        engine: &EngineInterface,
         call: &EvaluatedCall,
         _input: PipelineData,
     ) -> Result<PipelineData, LabeledError> {
         let cfg = PortListConfig::try_from(call)?;
-        let result = collect_socket_info(cfg)?;
+        let result = collect_socket_info(cfg, engine.signals(), call.head)?;
         Ok(PipelineData::Value(result, None))
     }
 }
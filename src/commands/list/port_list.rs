@@ -1,48 +1,85 @@
-use std::{collections::HashMap, net::IpAddr, vec};
+use std::{collections::HashSet, net::IpAddr, vec};
 
 use netstat2::{
     ProtocolSocketInfo, SocketInfo, TcpSocketInfo, TcpState, UdpSocketInfo, get_sockets_info,
 };
-use nu_protocol::{LabeledError, Span, Value};
-use sysinfo::{Process, System};
+use nu_protocol::{LabeledError, Signals, Span, Value};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
 
 use super::{
     PortListConfig,
     connection_info::{ConnectionInfo, ConnectionInfoBuilder, ProcessInfo},
 };
 
-pub(super) fn collect_socket_info(cfg: PortListConfig) -> Result<Value, LabeledError> {
-    let system = System::new_all();
-    let mut process_list: HashMap<u32, &Process> = HashMap::new();
+/// Lists the sockets, ctrl-c is checked between the expensive steps and for every socket
+pub(super) fn collect_socket_info(
+    cfg: PortListConfig,
+    signals: &Signals,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let connections = collect_connections(cfg, signals, span, |_| true)?;
+    let result = connections
+        .into_iter()
+        .map(|f| Value::record(f.into(), Span::unknown()));
+
+    Ok(Value::list(result.collect(), Span::unknown()))
+}
+
+/// Sockets selected by `cfg` and `keep`. With `process_info` only the processes owning
+/// the kept sockets are looked up.
+pub(crate) fn collect_connections(
+    cfg: PortListConfig,
+    signals: &Signals,
+    span: Span,
+    keep: impl Fn(&ConnectionInfo) -> bool,
+) -> Result<Vec<ConnectionInfo>, LabeledError> {
+    let mut connections = sockets(cfg, signals, span)?;
+    connections.retain(keep);
     if *cfg.process_info() {
-        process_list = system
-            .processes()
+        let pids = connections
             .iter()
-            .map(|(pid, process)| (pid.as_u32(), process))
-            .collect();
+            .map(|connection| Pid::from_u32(connection.pid))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let system = processes(
+            ProcessesToUpdate::Some(&pids),
+            ProcessRefreshKind::everything(),
+        );
+        signals.check(&span)?;
+        for connection in &mut connections {
+            connection.process_info = system
+                .process(Pid::from_u32(connection.pid))
+                .map(ProcessInfo::from);
+        }
     }
-    let connections = sockets(&process_list, cfg)?;
-    let result = connections
-        .iter()
-        .map(|f| Value::record(f.to_owned().into(), Span::unknown()));
+    Ok(connections)
+}
 
-    Ok(Value::list(result.collect(), Span::unknown()))
+/// Loads the requested details of the given processes
+pub(crate) fn processes(which: ProcessesToUpdate<'_>, details: ProcessRefreshKind) -> System {
+    let mut system = System::new();
+    system.refresh_processes_specifics(which, true, details);
+    system
 }
 
 fn sockets(
-    processes: &HashMap<u32, &Process>,
     cfg: PortListConfig,
+    signals: &Signals,
+    span: Span,
 ) -> Result<Vec<ConnectionInfo>, LabeledError> {
     let af = cfg.address_family_flags();
     let pf = cfg.protocol_flags();
     let sockets_info = get_sockets_info(af, pf).map_err(|e| {
         LabeledError::new(e.to_string()).with_code("netstat2::get_sockets_info::error")
     })?;
-    let connections = sockets_info
-        .iter()
-        .map(|i| socket2connection_info(cfg, processes, i))
-        .flatten();
+    let mut connections = vec![];
+    for info in &sockets_info {
+        signals.check(&span)?;
+        connections.extend(socket2connection_info(cfg, info));
+    }
     let result = connections
+        .into_iter()
         .map(|f| f.build().map_err(|e| LabeledError::new(e.to_string())))
         .collect();
     let (answer, errs) = split_results(result);
@@ -64,29 +101,21 @@ fn split_results<A, E>(input: Vec<Result<A, E>>) -> (Vec<A>, Vec<E>) {
     (ok_vec, err_vec)
 }
 
-fn socket2connection_info(
-    cfg: PortListConfig,
-    processes: &HashMap<u32, &Process>,
-    si: &SocketInfo,
-) -> Vec<ConnectionInfoBuilder> {
+fn socket2connection_info(cfg: PortListConfig, si: &SocketInfo) -> Vec<ConnectionInfoBuilder> {
     match si.to_owned().protocol_socket_info {
         ProtocolSocketInfo::Tcp(tcp_socket_info)
             if *cfg.tcp()
                 && (!cfg.listeners_only() || tcp_socket_info.state == TcpState::Listen) =>
         {
-            tcp2connection_info(processes, si, tcp_socket_info)
+            tcp2connection_info(si, tcp_socket_info)
         }
         ProtocolSocketInfo::Udp(udp_socket_info) if *cfg.udp() => {
-            udp2connection_info(processes, si, udp_socket_info)
+            udp2connection_info(si, udp_socket_info)
         }
         _ => vec![],
     }
 }
-fn tcp2connection_info(
-    processes: &HashMap<u32, &Process>,
-    si: &SocketInfo,
-    tsi: TcpSocketInfo,
-) -> Vec<ConnectionInfoBuilder> {
+fn tcp2connection_info(si: &SocketInfo, tsi: TcpSocketInfo) -> Vec<ConnectionInfoBuilder> {
     si.associated_pids
         .iter()
         .map(|pid| {
@@ -99,21 +128,12 @@ fn tcp2connection_info(
                 .remote_address(Some(tsi.remote_addr.to_string()))
                 .remote_port(Some(tsi.remote_port))
                 .state(tsi.state.to_string())
-                .process_info(
-                    processes
-                        .get(pid)
-                        .map(|process| ProcessInfo::from(process.to_owned()))
-                        .map_or(None, |f| Some(f)),
-                )
+                .process_info(None)
                 .to_owned()
         })
         .collect()
 }
-fn udp2connection_info(
-    processes: &HashMap<u32, &Process>,
-    si: &SocketInfo,
-    usi: UdpSocketInfo,
-) -> Vec<ConnectionInfoBuilder> {
+fn udp2connection_info(si: &SocketInfo, usi: UdpSocketInfo) -> Vec<ConnectionInfoBuilder> {
     si.associated_pids
         .iter()
         .map(|pid| {
@@ -126,11 +146,7 @@ fn udp2connection_info(
                 .remote_address(None)
                 .remote_port(None)
                 .state("LISTEN".to_string())
-                .process_info(
-                    processes
-                        .get(pid)
-                        .map(|process| ProcessInfo::from(process.to_owned())),
-                )
+                .process_info(None)
                 .to_owned()
         })
         .collect()
@@ -0,0 +1,347 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use nu_protocol::{LabeledError, ShellError, Span, Value, record};
+
+use crate::{commands::scan::Interrupt, helpers::AsValue};
+
+use super::ListenConfig;
+
+/// Largest payload a single udp datagram can carry
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// Received data kept per event, the `bytes` column still counts everything
+const DATA_LIMIT: usize = 64 * 1024;
+
+/// How often a listener without pending connections looks again
+const ACCEPT_POLL: Duration = Duration::from_millis(20);
+
+enum Socket {
+    Tcp(TcpListener),
+    Udp(UdpSocket),
+}
+
+/// Accepts connections or datagrams, yielding a record for each.
+/// Ends after `--count` events, once `--timeout` is over or on ctrl-c.
+pub(super) struct Listener {
+    socket: Socket,
+    cfg: ListenConfig,
+    interrupt: Interrupt,
+    deadline: Option<Instant>,
+    events: u64,
+    span: Span,
+    finished: bool,
+}
+
+/// Why waiting for the next event stopped without one
+enum Stop {
+    Done,
+    Failed(io::Error),
+}
+
+impl From<io::Error> for Stop {
+    fn from(err: io::Error) -> Self {
+        Stop::Failed(err)
+    }
+}
+
+impl Listener {
+    pub fn bind(cfg: ListenConfig, interrupt: Interrupt, span: Span) -> Result<Self, LabeledError> {
+        let address = SocketAddr::new(*cfg.address(), *cfg.port());
+        let socket = match cfg.udp() {
+            true => UdpSocket::bind(address).map(Socket::Udp),
+            false => TcpListener::bind(address)
+                .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+                .map(Socket::Tcp),
+        }
+        .map_err(|e| {
+            LabeledError::new(format!("cannot listen on {}: {}", address, e))
+                .with_label("`port check-bind` explains why", *cfg.port_span())
+        })?;
+        Ok(Self {
+            socket,
+            deadline: cfg.timeout().map(|timeout| Instant::now() + timeout),
+            cfg,
+            interrupt,
+            events: 0,
+            span,
+            finished: false,
+        })
+    }
+
+    /// Time left before `--timeout`, `None` without one
+    fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    fn next_event(&mut self) -> Result<Value, Stop> {
+        match &self.socket {
+            Socket::Tcp(listener) => {
+                let (stream, peer) = loop {
+                    if self.interrupt.triggered() || self.remaining().is_some_and(|r| r.is_zero()) {
+                        return Err(Stop::Done);
+                    }
+                    match listener.accept() {
+                        Ok(accepted) => break accepted,
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                            let wait = self.remaining().map_or(ACCEPT_POLL, |r| r.min(ACCEPT_POLL));
+                            self.interrupt.sleep(wait).map_err(|_| Stop::Done)?;
+                        }
+                        Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                        Err(err) => return Err(Stop::Failed(err)),
+                    }
+                };
+                self.connection(stream, peer)
+            }
+            Socket::Udp(socket) => {
+                let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+                let received = self.interrupt.read(
+                    self.remaining(),
+                    |slice| socket.set_read_timeout(Some(slice)),
+                    || socket.recv_from(&mut buffer),
+                );
+                let (size, peer) = match received {
+                    Ok(received) => received,
+                    Err(_) if self.interrupt.triggered() => return Err(Stop::Done),
+                    Err(err)
+                        if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                    {
+                        return Err(Stop::Done);
+                    }
+                    Err(err) => return Err(Stop::Failed(err)),
+                };
+                let started = Local::now().fixed_offset();
+                let (replied, error) = match self.cfg.reply() {
+                    Some(reply) => match socket.send_to(reply, peer) {
+                        Ok(_) => (true, None),
+                        Err(err) => (false, Some(err.to_string())),
+                    },
+                    None => (false, None),
+                };
+                let span = self.span;
+                Ok(record! {
+                    "protocol" => "udp".as_value(span),
+                    "peer_address" => peer.ip().to_string().as_value(span),
+                    "peer_port" => peer.port().as_value(span),
+                    "local_port" => socket.local_addr()?.port().as_value(span),
+                    "started" => started.as_value(span),
+                    "duration" => Option::<Duration>::None.as_value(span),
+                    "bytes" => (size as i64).as_value(span),
+                    "data" => buffer[..size.min(DATA_LIMIT)].to_vec().as_value(span),
+                    "replied" => replied.as_value(span),
+                    "error" => error.as_value(span),
+                }
+                .as_value(span))
+            }
+        }
+    }
+
+    /// Reads a tcp connection until the peer closes it or stays quiet for `--idle`.
+    /// The reply goes out after the first data, or after a quiet `--idle` for peers
+    /// waiting for a banner.
+    fn connection(&self, stream: TcpStream, peer: SocketAddr) -> Result<Value, Stop> {
+        let started = Local::now().fixed_offset();
+        let began = Instant::now();
+        stream.set_nonblocking(false)?;
+        let local_port = stream.local_addr()?.port();
+        let mut data = vec![];
+        let mut bytes = 0;
+        let mut replied = false;
+        let mut error = None;
+        let mut chunk = [0; 4096];
+        loop {
+            let wait = self
+                .remaining()
+                .map_or(*self.cfg.idle(), |r| r.min(*self.cfg.idle()));
+            if wait.is_zero() {
+                break;
+            }
+            let read = self.interrupt.read(
+                Some(wait),
+                |slice| stream.set_read_timeout(Some(slice)),
+                || (&stream).read(&mut chunk),
+            );
+            let quiet = match read {
+                Ok(0) => break,
+                Ok(size) => {
+                    bytes += size;
+                    let keep = size.min(DATA_LIMIT - data.len());
+                    data.extend_from_slice(&chunk[..keep]);
+                    false
+                }
+                Err(_) if self.interrupt.triggered() => return Err(Stop::Done),
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    true
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    error = Some(err.to_string());
+                    break;
+                }
+            };
+            match self.cfg.reply() {
+                Some(reply) if !replied => {
+                    replied = true;
+                    if let Err(err) = (&stream).write_all(reply) {
+                        error = Some(err.to_string());
+                        break;
+                    }
+                }
+                _ if quiet => break,
+                _ => {}
+            }
+        }
+        let span = self.span;
+        Ok(record! {
+            "protocol" => "tcp".as_value(span),
+            "peer_address" => peer.ip().to_string().as_value(span),
+            "peer_port" => peer.port().as_value(span),
+            "local_port" => local_port.as_value(span),
+            "started" => started.as_value(span),
+            "duration" => began.elapsed().as_value(span),
+            "bytes" => (bytes as i64).as_value(span),
+            "data" => data.as_value(span),
+            "replied" => replied.as_value(span),
+            "error" => error.as_value(span),
+        }
+        .as_value(span))
+    }
+}
+
+impl Iterator for Listener {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        if self.finished || self.cfg.count().is_some_and(|count| self.events >= count) {
+            return None;
+        }
+        match self.next_event() {
+            Ok(event) => {
+                self.events += 1;
+                Some(event)
+            }
+            Err(Stop::Done) => None,
+            Err(Stop::Failed(err)) => {
+                self.finished = true;
+                let err = LabeledError::new(format!("listening failed: {}", err))
+                    .with_label("while listening here", self.span);
+                Some(Value::error(ShellError::from(err), self.span))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        thread,
+    };
+
+    use crate::commands::listen::listen_config::ListenConfigBuilder;
+
+    use super::*;
+
+    fn listener(udp: bool, count: Option<u64>, timeout: Option<Duration>) -> Listener {
+        let cfg = ListenConfigBuilder::default()
+            .port(0)
+            .port_span(Span::test_data())
+            .address(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .udp(udp)
+            .count(count)
+            .timeout(timeout)
+            .idle(Duration::from_millis(200))
+            .reply(Some(b"pong".to_vec()))
+            .build()
+            .unwrap();
+        Listener::bind(cfg, Interrupt::default(), Span::test_data()).unwrap()
+    }
+
+    fn local_address(listener: &Listener) -> SocketAddr {
+        match &listener.socket {
+            Socket::Tcp(listener) => listener.local_addr(),
+            Socket::Udp(socket) => socket.local_addr(),
+        }
+        .unwrap()
+    }
+
+    fn assert_event(event: &Value, protocol: &str, peer: SocketAddr, local: SocketAddr) {
+        let event = event.as_record().unwrap();
+        assert_eq!(event.get("protocol"), Some(&Value::test_string(protocol)));
+        assert_eq!(
+            event.get("peer_address"),
+            Some(&Value::test_string(peer.ip().to_string()))
+        );
+        assert_eq!(
+            event.get("peer_port"),
+            Some(&Value::test_int(i64::from(peer.port())))
+        );
+        assert_eq!(
+            event.get("local_port"),
+            Some(&Value::test_int(i64::from(local.port())))
+        );
+        assert_eq!(event.get("bytes"), Some(&Value::test_int(4)));
+        assert_eq!(event.get("data"), Some(&Value::test_binary(b"ping")));
+        assert_eq!(event.get("replied"), Some(&Value::test_bool(true)));
+        assert_eq!(event.get("error"), Some(&Value::test_nothing()));
+    }
+
+    #[test]
+    fn tcp_connection_is_recorded_and_answered() {
+        let mut listener = listener(false, Some(1), Some(Duration::from_secs(5)));
+        let address = local_address(&listener);
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"ping").unwrap();
+            let mut reply = vec![];
+            // the listener closes the connection once the client stays quiet for `--idle`
+            stream.read_to_end(&mut reply).unwrap();
+            (stream.local_addr().unwrap(), reply)
+        });
+        let event = listener.next().unwrap();
+        let (peer, reply) = client.join().unwrap();
+
+        assert_event(&event, "tcp", peer, address);
+        assert_eq!(reply, b"pong");
+        // `--count 1` ends the stream without waiting for the timeout
+        let started = Instant::now();
+        assert!(listener.next().is_none());
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn udp_datagram_is_recorded_and_answered() {
+        let mut listener = listener(true, Some(1), Some(Duration::from_secs(5)));
+        let address = local_address(&listener);
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client.send_to(b"ping", address).unwrap();
+        let event = listener.next().unwrap();
+        let mut reply = [0; 16];
+        let (size, from) = client.recv_from(&mut reply).unwrap();
+
+        assert_event(&event, "udp", client.local_addr().unwrap(), address);
+        assert_eq!(&reply[..size], b"pong");
+        assert_eq!(from, address);
+        assert!(listener.next().is_none());
+    }
+
+    #[test]
+    fn timeout_ends_the_stream() {
+        for udp in [false, true] {
+            let listener = listener(udp, None, Some(Duration::from_millis(100)));
+            let started = Instant::now();
+            assert_eq!(listener.count(), 0);
+            let elapsed = started.elapsed();
+            assert!(elapsed >= Duration::from_millis(100), "{elapsed:?}");
+            assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    str::FromStr,
+    time::Duration,
+};
+
+use derive_builder::Builder;
+use derive_getters::Getters;
+use nu_plugin::EvaluatedCall;
+use nu_protocol::{LabeledError, Span, Value};
+
+use crate::helpers::FlagHelper;
+
+const DEFAULT_IDLE: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Builder, Debug, Getters)]
+pub struct ListenConfig {
+    /// Port to listen on, 0 lets the OS pick one
+    port: u16,
+    port_span: Span,
+    address: IpAddr,
+    /// Receive datagrams instead of accepting tcp connections
+    udp: bool,
+    /// Stop after this many connections or datagrams
+    count: Option<u64>,
+    /// Stop listening after this long
+    timeout: Option<Duration>,
+    /// A tcp connection ends once the peer stayed quiet for this long
+    idle: Duration,
+    /// Sent back to every connection or datagram
+    reply: Option<Vec<u8>>,
+}
+
+impl TryFrom<&EvaluatedCall> for ListenConfig {
+    type Error = LabeledError;
+
+    fn try_from(call: &EvaluatedCall) -> Result<Self, Self::Error> {
+        let mut builder = ListenConfigBuilder::create_empty();
+        let port = call.req::<i64>(0)?;
+        builder.port(u16::try_from(port).map_err(|_| {
+            LabeledError::new("invalid port")
+                .with_label("not a valid port number", call.positional[0].span())
+        })?);
+        builder.port_span(call.positional[0].span());
+        builder.address(match call.get_flag::<String>("address")? {
+            Some(address) => IpAddr::from_str(&address).map_err(|e| {
+                LabeledError::new(format!("invalid address: {}", e)).with_label(
+                    "expected an IP address",
+                    call.get_flag_span("address").unwrap_or(call.head),
+                )
+            })?,
+            None => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        });
+        builder.udp(call.has_flag_or("udp", false));
+        builder.count(match call.get_flag_value("count") {
+            Some(value) => match value.as_int()? {
+                count if count > 0 => Some(count as u64),
+                _ => {
+                    return Err(LabeledError::new("invalid count")
+                        .with_label("expected a positive number of events", value.span()));
+                }
+            },
+            None => None,
+        });
+        builder.timeout(duration_flag(call, "timeout")?);
+        builder.idle(duration_flag(call, "idle")?.unwrap_or(DEFAULT_IDLE));
+        builder.reply(match call.get_flag_value("reply") {
+            Some(Value::String { val, .. }) => Some(val.into_bytes()),
+            Some(Value::Binary { val, .. }) => Some(val),
+            _ => None,
+        });
+        builder
+            .build()
+            .map_err(|e| LabeledError::new(e.to_string()))
+    }
+}
+
+/// Reads an optional positive duration flag
+fn duration_flag(call: &EvaluatedCall, flag: &str) -> Result<Option<Duration>, LabeledError> {
+    let Some(value) = call.get_flag_value(flag) else {
+        return Ok(None);
+    };
+    match value.as_duration()? {
+        nanos if nanos > 0 => Ok(Some(Duration::from_nanos(nanos as u64))),
+        _ => Err(LabeledError::new(format!("invalid `--{}`", flag))
+            .with_label("expected a positive duration", value.span())),
+    }
+}
@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod listen;
+mod listen_config;
+mod plugin;
+pub(super) use listen_config::ListenConfig;
+pub(crate) use plugin::PortListen;
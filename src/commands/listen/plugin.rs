@@ -0,0 +1,112 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, ListStream, PipelineData, Signals, Signature, SyntaxShape,
+};
+
+use crate::PortExtension;
+use crate::commands::scan::Interrupt;
+
+use super::{ListenConfig, listen::Listener};
+
+#[derive(Default)]
+pub struct PortListen;
+
+impl PortListen {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PluginCommand for PortListen {
+    type Plugin = PortExtension;
+
+    fn name(&self) -> &str {
+        "port listen"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("port listen")
+            .required(
+                "port",
+                SyntaxShape::Int,
+                "local port to listen on, 0 lets the OS pick one",
+            )
+            .named(
+                "address",
+                SyntaxShape::String,
+                "local address to listen on (default: 0.0.0.0)",
+                Some('a'),
+            )
+            .switch(
+                "udp",
+                "receive udp datagrams instead of tcp connections",
+                Some('u'),
+            )
+            .named(
+                "count",
+                SyntaxShape::Int,
+                "stop after this many connections or datagrams",
+                Some('c'),
+            )
+            .named(
+                "timeout",
+                SyntaxShape::Duration,
+                "stop listening after this long",
+                Some('t'),
+            )
+            .named(
+                "idle",
+                SyntaxShape::Duration,
+                "end a tcp connection once the peer stayed quiet for this long (default: 1 Second)",
+                None,
+            )
+            .named(
+                "reply",
+                SyntaxShape::OneOf(vec![SyntaxShape::String, SyntaxShape::Binary]),
+                "data sent back to every connection or datagram",
+                Some('r'),
+            )
+            .category(Category::Network)
+    }
+
+    fn description(&self) -> &str {
+        "Listens on a local port and streams a record per connection or datagram received, the counterpart of `port scan`"
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "port listen 8080 --count 1 | get data | decode",
+                description: "show what the first client connecting to port 8080 sends",
+                result: None,
+            },
+            Example {
+                example: "port listen 2525 --reply \"220 test ESMTP\\r\\n\" --timeout 1min",
+                description: "stand in for an smtp server for a minute",
+                result: None,
+            },
+            Example {
+                example: "port listen 5353 --udp --address 127.0.0.1 --reply 0x[00 01]",
+                description: "record the datagrams sent to a local udp port and answer each one",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let cfg = ListenConfig::try_from(call)?;
+        let interrupt = Interrupt::new(engine.signals().clone());
+        let listener = Listener::bind(cfg, interrupt, call.head)?;
+        // the listener notices ctrl-c while it waits and ends the stream quietly
+        Ok(PipelineData::list_stream(
+            ListStream::new(listener, call.head, Signals::empty()),
+            None,
+        ))
+    }
+}
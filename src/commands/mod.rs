@@ -1,2 +1,9 @@
+pub(crate) mod check_bind;
+pub(crate) mod free;
+pub(crate) mod kill;
 pub(crate) mod list;
+pub(crate) mod listen;
+pub(crate) mod ping;
 pub(crate) mod scan;
+pub(crate) mod wait;
+pub(crate) mod who;
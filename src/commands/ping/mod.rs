@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod ping;
+mod ping_config;
+mod plugin;
+pub(super) use ping_config::PingConfig;
+pub(crate) use plugin::PortPing;
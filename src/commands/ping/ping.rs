@@ -0,0 +1,146 @@
+use std::time::{Duration, Instant};
+
+use nu_protocol::{LabeledError, ShellError, Span, Value, record};
+
+use crate::{
+    commands::scan::{Reachability, Reply},
+    helpers::AsValue,
+};
+
+use super::PingConfig;
+
+/// Probes the target every interval, yielding a record per probe and a summary at the end.
+/// Ctrl-c ends the probes early, the summary still covers the ones already sent.
+pub(super) struct Pinger {
+    target: Reachability,
+    cfg: PingConfig,
+    span: Span,
+    sent: u64,
+    /// Round-trip times of the answered probes, in order
+    rtts: Vec<Duration>,
+    last_probe: Option<Instant>,
+    finished: bool,
+}
+
+impl Pinger {
+    pub fn new(target: Reachability, cfg: PingConfig, span: Span) -> Self {
+        Self {
+            target,
+            cfg,
+            span,
+            sent: 0,
+            rtts: vec![],
+            last_probe: None,
+            finished: false,
+        }
+    }
+
+    fn probe(&mut self) -> Result<Value, LabeledError> {
+        self.last_probe = Some(Instant::now());
+        let reply = self.target.probe()?;
+        self.sent += 1;
+        let span = self.span;
+        let Reply { answer, rtt, error } = reply;
+        if answer.is_reply() {
+            self.rtts.push(rtt.unwrap_or_default());
+        }
+        Ok(record! {
+            "seq" => Value::int(self.sent as i64, span),
+            "address" => self.target.address().as_value(span),
+            "port" => self.target.port().as_value(span),
+            "status" => answer.as_value(span),
+            "rtt" => rtt.as_value(span),
+            "error" => error.as_value(span),
+        }
+        .as_value(span))
+    }
+
+    fn summary(&self) -> Value {
+        let span = self.span;
+        let received = self.rtts.len() as u64;
+        let loss = match self.sent {
+            0 => 0.0,
+            sent => (sent - received) as f64 * 100.0 / sent as f64,
+        };
+        let stats = Stats::of(&self.rtts);
+        record! {
+            "address" => self.target.address().as_value(span),
+            "port" => self.target.port().as_value(span),
+            "sent" => Value::int(self.sent as i64, span),
+            "received" => Value::int(received as i64, span),
+            "loss" => Value::float(loss, span),
+            "min" => stats.as_ref().map(|stats| stats.min).as_value(span),
+            "avg" => stats.as_ref().map(|stats| stats.avg).as_value(span),
+            "max" => stats.as_ref().map(|stats| stats.max).as_value(span),
+            "stddev" => stats.as_ref().map(|stats| stats.stddev).as_value(span),
+            "jitter" => stats.as_ref().and_then(|stats| stats.jitter).as_value(span),
+        }
+        .as_value(span)
+    }
+
+    /// Waits for the next probe, `false` once done or interrupted
+    fn wait(&self) -> bool {
+        if self.cfg.count().is_some_and(|count| self.sent >= count) {
+            return false;
+        }
+        match self.last_probe {
+            Some(last) => self
+                .target
+                .sleep(self.cfg.interval().saturating_sub(last.elapsed())),
+            None => true,
+        }
+    }
+}
+
+impl Iterator for Pinger {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        if self.finished {
+            return None;
+        }
+        if self.wait() {
+            match self.probe() {
+                Ok(value) => return Some(value),
+                Err(err) if !self.target.interrupted() => {
+                    self.finished = true;
+                    return Some(Value::error(ShellError::from(err), self.span));
+                }
+                Err(_) => {}
+            }
+        }
+        self.finished = true;
+        Some(self.summary())
+    }
+}
+
+struct Stats {
+    min: Duration,
+    avg: Duration,
+    max: Duration,
+    stddev: Duration,
+    /// Mean difference between consecutive round-trip times, needs two answers
+    jitter: Option<Duration>,
+}
+
+impl Stats {
+    fn of(rtts: &[Duration]) -> Option<Self> {
+        let min = *rtts.iter().min()?;
+        let max = *rtts.iter().max()?;
+        let seconds = rtts.iter().map(Duration::as_secs_f64).collect::<Vec<_>>();
+        let mean = seconds.iter().sum::<f64>() / seconds.len() as f64;
+        let variance =
+            seconds.iter().map(|rtt| (rtt - mean).powi(2)).sum::<f64>() / seconds.len() as f64;
+        let jitter = (seconds.len() > 1).then(|| {
+            let differences = seconds.windows(2).map(|pair| (pair[1] - pair[0]).abs());
+            Duration::from_secs_f64(differences.sum::<f64>() / (seconds.len() - 1) as f64)
+        });
+        Some(Self {
+            min,
+            avg: Duration::from_secs_f64(mean),
+            max,
+            stddev: Duration::from_secs_f64(variance.sqrt()),
+            jitter,
+        })
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use derive_builder::Builder;
+use derive_getters::Getters;
+use nu_plugin::EvaluatedCall;
+use nu_protocol::LabeledError;
+
+const DEFAULT_COUNT: u64 = 4;
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Builder, Debug, Getters)]
+pub struct PingConfig {
+    /// Number of probes, `None` keeps probing until interrupted
+    count: Option<u64>,
+    /// Time between the start of two probes
+    interval: Duration,
+}
+
+impl TryFrom<&EvaluatedCall> for PingConfig {
+    type Error = LabeledError;
+
+    fn try_from(call: &EvaluatedCall) -> Result<Self, Self::Error> {
+        let mut builder = PingConfigBuilder::create_empty();
+        let count = call.get_flag::<i64>("count")?;
+        builder.count(match count {
+            None => Some(DEFAULT_COUNT),
+            Some(0) => None,
+            Some(count) => Some(u64::try_from(count).map_err(|_| {
+                LabeledError::new("invalid count").with_label(
+                    "expected a positive number, or 0 to ping until interrupted",
+                    call.get_flag_span("count").unwrap_or(call.head),
+                )
+            })?),
+        });
+        let interval = match call.get_flag_value("interval") {
+            Some(value) => u64::try_from(value.as_duration()?)
+                .map(Duration::from_nanos)
+                .map_err(|_| {
+                    LabeledError::new("invalid interval")
+                        .with_label("the interval cannot be negative", value.span())
+                })?,
+            None => DEFAULT_INTERVAL,
+        };
+        builder.interval(interval);
+        builder
+            .build()
+            .map_err(|e| LabeledError::new(e.to_string()))
+    }
+}
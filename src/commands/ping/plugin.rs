@@ -0,0 +1,128 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Category, Example, LabeledError, ListStream, PipelineData, Signals, Signature, SyntaxShape,
+};
+
+use crate::PortExtension;
+use crate::commands::scan::Reachability;
+
+use super::{PingConfig, ping::Pinger};
+
+#[derive(Default)]
+pub struct PortPing;
+
+impl PortPing {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PluginCommand for PortPing {
+    type Plugin = PortExtension;
+
+    fn name(&self) -> &str {
+        "port ping"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("port ping")
+            .required("host", SyntaxShape::String, "IP address or host name to ping")
+            .required("port", SyntaxShape::Int, "port to connect to")
+            .named(
+                "count",
+                SyntaxShape::Int,
+                "number of probes, 0 pings until interrupted (default: 4)",
+                Some('n'),
+            )
+            .named(
+                "interval",
+                SyntaxShape::Duration,
+                "time between the start of two probes (default: 1 Second)",
+                Some('i'),
+            )
+            .named(
+                "timeout",
+                SyntaxShape::Duration,
+                "time before a probe counts as lost (default: 1 Second)",
+                Some('t'),
+            )
+            .switch(
+                "udp",
+                "send udp datagrams instead of opening tcp connections, only replies and ICMP port-unreachable count as answers",
+                Some('u'),
+            )
+            .named(
+                "send",
+                SyntaxShape::OneOf(vec![SyntaxShape::String, SyntaxShape::Binary]),
+                "payload of the udp datagrams",
+                Some('s'),
+            )
+            .named(
+                "probe",
+                SyntaxShape::String,
+                "udp protocol probe generating the datagrams (dns, ntp, snmp, ssdp, stun, memcached)",
+                Some('p'),
+            )
+            .named(
+                "bind",
+                SyntaxShape::String,
+                "local address (`ip` or `ip:port`) the probes are sent from",
+                None,
+            )
+            .named(
+                "interface",
+                SyntaxShape::String,
+                "network interface the probes are sent through (linux only)",
+                None,
+            )
+            .named("ttl", SyntaxShape::Int, "time to live (hop limit) of the probes", None)
+            .named(
+                "tos",
+                SyntaxShape::Int,
+                "type of service (traffic class) byte of the probes",
+                None,
+            )
+            .category(Category::Network)
+    }
+
+    fn description(&self) -> &str {
+        "Measures the connect latency of a port like `tcping`, yielding a record per probe and a summary with loss and round-trip statistics"
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "port ping 1.1.1.1 443",
+                description: "connect four times to port 443 of 1.1.1.1, one second apart",
+                result: None,
+            },
+            Example {
+                example: "port ping example.com 80 --count 0 --interval 200ms",
+                description: "ping until ctrl-c is pressed, the summary still comes last",
+                result: None,
+            },
+            Example {
+                example: "port ping 8.8.8.8 53 --udp --probe dns -n 10 | last",
+                description: "only keep the summary of ten dns queries",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let cfg = PingConfig::try_from(call)?;
+        let target = Reachability::new(call, engine)?;
+        let pinger = Pinger::new(target, cfg, call.head);
+        // the pinger stops on ctrl-c by itself, so the summary is not cut off
+        Ok(PipelineData::list_stream(
+            ListStream::new(pinger, call.head, Signals::empty()),
+            None,
+        ))
+    }
+}
@@ -0,0 +1,100 @@
+use nu_plugin::EngineInterface;
+use nu_protocol::{LabeledError, Span, Spanned, Value, engine::Closure};
+
+use crate::helpers::AsValue;
+
+use super::scan_result::PortState;
+
+/// Runs the `--check` closure with the scan result as argument and pipeline input.
+/// A bool returned by the closure decides `is_open` and `state`, a record is merged into the result.
+pub(super) fn apply(
+    engine: &EngineInterface,
+    check: &Spanned<Closure>,
+    result: Value,
+) -> Result<Value, LabeledError> {
+    let verdict = engine.eval_closure(check, vec![result.clone()], Some(result.clone()))?;
+    merge(result, verdict, check.span)
+}
+
+/// Folds the closure's verdict into the scan result
+fn merge(result: Value, verdict: Value, check_span: Span) -> Result<Value, LabeledError> {
+    let span = result.span();
+    let mut record = result.into_record()?;
+    match verdict {
+        Value::Bool { val, .. } => {
+            let state = if val {
+                PortState::Open
+            } else {
+                PortState::Closed
+            };
+            record.insert("is_open", Value::bool(val, span));
+            record.insert("state", state.as_value(span));
+        }
+        Value::Record { val, .. } => {
+            for (column, value) in val.into_owned() {
+                record.insert(column, value);
+            }
+        }
+        other => {
+            return Err(
+                LabeledError::new("`--check` must return a bool or a record").with_label(
+                    format!("this closure returned {}", other.get_type()),
+                    check_span,
+                ),
+            );
+        }
+    }
+    Ok(Value::record(record, span))
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::record;
+
+    use super::*;
+
+    fn result(state: &str) -> Value {
+        Value::test_record(record! {
+            "port" => Value::test_int(22),
+            "state" => Value::test_string(state),
+            "is_open" => Value::test_bool(state != "closed"),
+        })
+    }
+
+    #[test]
+    fn false_verdict_closes_the_port() {
+        let merged = merge(result("open"), Value::test_bool(false), Span::test_data()).unwrap();
+        let merged = merged.as_record().unwrap();
+        assert_eq!(merged.get("is_open"), Some(&Value::test_bool(false)));
+        assert_eq!(merged.get("state"), Some(&Value::test_string("closed")));
+    }
+
+    #[test]
+    fn true_verdict_confirms_an_open_filtered_port() {
+        let merged = merge(
+            result("open|filtered"),
+            Value::test_bool(true),
+            Span::test_data(),
+        )
+        .unwrap();
+        let merged = merged.as_record().unwrap();
+        assert_eq!(merged.get("is_open"), Some(&Value::test_bool(true)));
+        assert_eq!(merged.get("state"), Some(&Value::test_string("open")));
+    }
+
+    #[test]
+    fn record_verdict_is_merged() {
+        let verdict = Value::test_record(record! {
+            "service" => Value::test_string("ssh"),
+        });
+        let merged = merge(result("open"), verdict, Span::test_data()).unwrap();
+        let merged = merged.as_record().unwrap();
+        assert_eq!(merged.get("service"), Some(&Value::test_string("ssh")));
+        assert_eq!(merged.get("state"), Some(&Value::test_string("open")));
+    }
+
+    #[test]
+    fn other_verdicts_are_rejected() {
+        assert!(merge(result("open"), Value::test_int(1), Span::test_data()).is_err());
+    }
+}
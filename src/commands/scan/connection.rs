@@ -0,0 +1,88 @@
+use std::{
+    io::{self, Read, Write},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use super::{
+    interrupt::SignalStream,
+    probe_error::ProbeError,
+    scan_config::ScanConfig,
+    socket::SocketSetting,
+    tls::{self, StartTls, TlsReport, TlsStream},
+};
+
+/// Open connection to the target, either plain TCP or TLS
+pub(super) trait Connection: Read + Write {
+    /// Changes the read timeout of the underlying tcp socket
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl Connection for SignalStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        SignalStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl Connection for TlsStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+}
+
+/// How the TCP connection gets upgraded to TLS
+pub(super) struct TlsTarget<'a> {
+    pub sni: Option<&'a str>,
+    pub starttls: Option<StartTls>,
+    pub alpn: &'a [&'a str],
+}
+
+pub(super) struct Session {
+    /// `None` when the TLS handshake failed, `tls` describes why
+    pub connection: Option<Box<dyn Connection>>,
+    pub tls: Option<TlsReport>,
+    pub connect_time: Duration,
+    pub tls_time: Option<Duration>,
+}
+
+/// Connects to `address` and performs the TLS handshake when `tls` is given
+pub(super) fn open(
+    address: SocketAddr,
+    cfg: &ScanConfig,
+    tls: Option<TlsTarget>,
+) -> Result<Session, ProbeError> {
+    let timeout = *cfg.timeout();
+    let started = Instant::now();
+    let stream = cfg
+        .socket()
+        .connect_tcp(address, timeout, cfg.interrupt())?;
+    let connect_time = started.elapsed();
+    stream
+        .set_read_timeout(Some(timeout))
+        .map_err(|e| ProbeError::socket_option(SocketSetting::Timeout, e))?;
+    stream
+        .set_write_timeout(Some(timeout))
+        .map_err(|e| ProbeError::socket_option(SocketSetting::Timeout, e))?;
+    let Some(target) = tls else {
+        return Ok(Session {
+            connection: Some(Box::new(stream)),
+            tls: None,
+            connect_time,
+            tls_time: None,
+        });
+    };
+    let handshake_started = Instant::now();
+    let (report, tls_stream) = tls::handshake(
+        stream,
+        address.ip(),
+        target.sni,
+        target.starttls,
+        target.alpn,
+    );
+    Ok(Session {
+        connection: tls_stream.map(|s| Box::new(s) as Box<dyn Connection>),
+        tls: Some(report),
+        connect_time,
+        tls_time: Some(handshake_started.elapsed()),
+    })
+}
@@ -0,0 +1,209 @@
+mod signature;
+pub(super) use signature::SignatureDb;
+
+use std::{
+    io::{ErrorKind, Write},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use nu_protocol::{Record, Span};
+
+use crate::helpers::AsValue;
+
+use super::{connection::Connection, interrupt::Interrupt, socket::SocketOptions};
+
+use signature::Probe;
+
+/// How long detection waits for the service to greet us
+const BANNER_WAIT: Duration = Duration::from_millis(500);
+/// How long a response may keep arriving after its first byte
+const RESPONSE_WINDOW: Duration = Duration::from_millis(200);
+const MAX_RESPONSE_SIZE: usize = 4096;
+
+#[derive(Default, Clone, Debug)]
+pub(super) struct Detection {
+    service: Option<String>,
+    product: Option<String>,
+    version: Option<String>,
+}
+
+impl Detection {
+    pub fn write_to(self, record: &mut Record, span: Span) {
+        record.insert("service", self.service.as_value(span));
+        record.insert("product", self.product.as_value(span));
+        record.insert("version", self.version.as_value(span));
+    }
+}
+
+/// Identifies the service listening on `address`.
+/// Data already received by the scan is matched first, then the `banner` the service sent on
+/// the scan's connection. Only when no banner arrived, every probe of the database is run
+/// over its own connection until one of the responses matches.
+pub(super) fn detect(
+    address: SocketAddr,
+    socket: &SocketOptions,
+    timeout: Duration,
+    interrupt: &Interrupt,
+    received: Option<&[u8]>,
+    banner: Option<&[u8]>,
+    db: &SignatureDb,
+) -> Detection {
+    if let Some(detection) = received
+        .filter(|data| !data.is_empty())
+        .and_then(|data| db.identify(None, data))
+    {
+        return detection;
+    }
+    if let Some(banner) = banner.filter(|banner| !banner.is_empty()) {
+        return db.identify(None, banner).unwrap_or_default();
+    }
+    db.probes()
+        .iter()
+        // the scan's connection already waited for a banner
+        .filter(|probe| banner.is_none() || !probe.payload().is_empty())
+        .take_while(|_| !interrupt.triggered())
+        .find_map(|probe| {
+            let response = run_probe(address, socket, timeout, interrupt, probe)?;
+            db.identify(Some(probe), &response)
+        })
+        .unwrap_or_default()
+}
+
+/// Waits up to `BANNER_WAIT` for the service to greet us on an open connection
+pub(super) fn read_banner(connection: &mut dyn Connection, timeout: Duration) -> Vec<u8> {
+    read_response(connection, timeout.min(BANNER_WAIT))
+}
+
+fn run_probe(
+    address: SocketAddr,
+    socket: &SocketOptions,
+    timeout: Duration,
+    interrupt: &Interrupt,
+    probe: &Probe,
+) -> Option<Vec<u8>> {
+    let mut stream = socket.connect_tcp(address, timeout, interrupt).ok()?;
+    let wait = match probe.payload().is_empty() {
+        true => timeout.min(BANNER_WAIT),
+        false => timeout,
+    };
+    stream.set_write_timeout(Some(timeout)).ok()?;
+    if !probe.payload().is_empty() {
+        stream.write_all(probe.payload()).ok()?;
+    }
+    Some(read_response(&mut stream, wait)).filter(|response| !response.is_empty())
+}
+
+/// Reads what arrives within `wait`, once the first byte is there the response may take
+/// `RESPONSE_WINDOW` more so segments sent after it are matched too. Stops early at eof
+/// or `MAX_RESPONSE_SIZE` bytes.
+fn read_response(connection: &mut dyn Connection, wait: Duration) -> Vec<u8> {
+    let mut deadline = Instant::now() + wait;
+    let mut response = vec![];
+    let mut chunk = [0; MAX_RESPONSE_SIZE];
+    while response.len() < MAX_RESPONSE_SIZE {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() || connection.set_read_timeout(Some(remaining)).is_err() {
+            break;
+        }
+        match connection.read(&mut chunk[..MAX_RESPONSE_SIZE - response.len()]) {
+            Ok(0) => break,
+            Ok(size) => {
+                if response.is_empty() {
+                    deadline = deadline.min(Instant::now() + RESPONSE_WINDOW);
+                }
+                response.extend_from_slice(&chunk[..size]);
+            }
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(_) => break,
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use super::{super::interrupt::SignalStream, *};
+
+    #[test]
+    fn banner_split_across_segments_is_read_whole() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"SSH-2.0-").unwrap();
+            thread::sleep(Duration::from_millis(50));
+            stream.write_all(b"OpenSSH_9.6\r\n").unwrap();
+            thread::sleep(Duration::from_millis(100));
+        });
+        let mut connection =
+            SignalStream::new(TcpStream::connect(address).unwrap(), Interrupt::default());
+        let banner = read_banner(&mut connection, Duration::from_secs(5));
+        server.join().unwrap();
+
+        assert_eq!(banner, b"SSH-2.0-OpenSSH_9.6\r\n");
+        let detection = detect(
+            address,
+            &SocketOptions::default(),
+            Duration::from_secs(1),
+            &Interrupt::default(),
+            None,
+            Some(&banner),
+            &SignatureDb::builtin(),
+        );
+        assert_eq!(detection.service.as_deref(), Some("ssh"));
+        assert_eq!(detection.product.as_deref(), Some("OpenSSH"));
+        assert_eq!(detection.version.as_deref(), Some("9.6"));
+    }
+
+    #[test]
+    fn unknown_banner_does_not_start_active_probes() {
+        // nothing listens on the address, an active probe would have nothing to connect to
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let detection = detect(
+            address,
+            &SocketOptions::default(),
+            Duration::from_secs(1),
+            &Interrupt::default(),
+            None,
+            Some(b"WELCOME TO THE MAINFRAME\r\n"),
+            &SignatureDb::builtin(),
+        );
+        assert_eq!(detection.service, None);
+    }
+
+    #[test]
+    fn silent_service_gets_active_probes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 64];
+            let size = stream.read(&mut request).unwrap();
+            assert!(request[..size].starts_with(b"HEAD / HTTP/1.0"));
+            stream
+                .write_all(b"HTTP/1.0 200 OK\r\nServer: nginx/1.25.3\r\n\r\n")
+                .unwrap();
+        });
+        let detection = detect(
+            address,
+            &SocketOptions::default(),
+            Duration::from_secs(5),
+            &Interrupt::default(),
+            None,
+            Some(b""),
+            &SignatureDb::builtin(),
+        );
+        server.join().unwrap();
+        assert_eq!(detection.service.as_deref(), Some("http"));
+    }
+}
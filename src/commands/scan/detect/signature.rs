@@ -0,0 +1,267 @@
+use derive_getters::Getters;
+use regex::bytes::{Regex, RegexBuilder};
+
+use super::Detection;
+
+const BUILTIN_SIGNATURES: &str = include_str!("signatures.txt");
+
+#[derive(Debug, Getters)]
+pub(crate) struct Probe {
+    #[getter(skip)]
+    name: String,
+    payload: Vec<u8>,
+    #[getter(skip)]
+    matches: Vec<Match>,
+}
+
+#[derive(Debug)]
+struct Match {
+    service: String,
+    pattern: Regex,
+    product: Option<String>,
+    version: Option<String>,
+}
+
+impl Match {
+    fn identify(&self, response: &[u8]) -> Option<Detection> {
+        let captures = self.pattern.captures(response)?;
+        let expand = |template: &Option<String>| {
+            template.as_ref().map(|template| {
+                let mut out = vec![];
+                captures.expand(template.as_bytes(), &mut out);
+                String::from_utf8_lossy(&out).trim().to_string()
+            })
+        };
+        Some(Detection {
+            service: Some(self.service.clone()),
+            product: expand(&self.product),
+            version: expand(&self.version),
+        })
+    }
+}
+
+/// Probes and match rules used to identify the service behind an open port,
+/// see `signatures.txt` for the file format
+#[derive(Debug, Default)]
+pub(crate) struct SignatureDb {
+    probes: Vec<Probe>,
+}
+
+impl SignatureDb {
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_SIGNATURES).expect("built-in signature database is valid")
+    }
+
+    /// Loads a user provided signature file, its entries take precedence over the built-in ones
+    pub fn with_file(path: &std::path::Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut db = Self::parse(&source)?;
+        db.probes.extend(Self::builtin().probes);
+        Ok(db)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut db = SignatureDb::default();
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            db.parse_line(line)
+                .map_err(|e| format!("line {}: {}", index + 1, e))?;
+        }
+        if db.probes.is_empty() {
+            return Err("no probes defined".into());
+        }
+        Ok(db)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let (directive, rest) = split_word(line);
+        match directive {
+            "probe" => {
+                let (name, rest) = split_word(rest);
+                if name.is_empty() {
+                    return Err("probe name is missing".into());
+                }
+                let payload = match rest.strip_prefix('q') {
+                    Some(quoted) => unescape(delimited(quoted)?.0)?,
+                    None if rest.is_empty() => vec![],
+                    None => return Err(format!("unexpected `{}` after probe name", rest)),
+                };
+                self.probes.push(Probe {
+                    name: name.to_string(),
+                    payload,
+                    matches: vec![],
+                });
+            }
+            "match" => {
+                let probe = self
+                    .probes
+                    .last_mut()
+                    .ok_or("`match` must follow a `probe` line")?;
+                let (service, rest) = split_word(rest);
+                let rest = rest
+                    .strip_prefix('m')
+                    .ok_or("expected a pattern such as `m|regex|`")?;
+                let (pattern, rest) = delimited(rest)?;
+                let (flags, mut rest) = split_word(rest);
+                let pattern = RegexBuilder::new(pattern)
+                    .unicode(false)
+                    .case_insensitive(flags.contains('i'))
+                    .dot_matches_new_line(flags.contains('s'))
+                    .build()
+                    .map_err(|e| e.to_string())?;
+                let mut product = None;
+                let mut version = None;
+                while !rest.is_empty() {
+                    let field = match rest.chars().next() {
+                        Some('p') => &mut product,
+                        Some('v') => &mut version,
+                        _ => return Err(format!("unknown field `{}`", rest)),
+                    };
+                    let (value, remaining) = delimited(&rest[1..])?;
+                    *field = Some(value.to_string());
+                    rest = remaining.trim_start();
+                }
+                probe.matches.push(Match {
+                    service: service.to_string(),
+                    pattern,
+                    product,
+                    version,
+                });
+            }
+            other => return Err(format!("unknown directive `{}`", other)),
+        }
+        Ok(())
+    }
+
+    pub fn probes(&self) -> &[Probe] {
+        &self.probes
+    }
+
+    /// Matches a response against the rules of the probe that produced it first
+    /// (including rules of other probes sharing its name), then against every other rule
+    pub fn identify(&self, probe: Option<&Probe>, response: &[u8]) -> Option<Detection> {
+        let is_own = |p: &&Probe| probe.is_some_and(|probe| probe.name == p.name);
+        let own = self.probes.iter().filter(is_own);
+        let others = self.probes.iter().filter(|p| !is_own(p));
+        own.chain(others)
+            .flat_map(|p| p.matches.iter())
+            .find_map(|m| m.identify(response))
+    }
+}
+
+fn split_word(input: &str) -> (&str, &str) {
+    match input.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim_start()),
+        None => (input, ""),
+    }
+}
+
+/// Splits `|value|rest` into `value` and `rest`, the first character is the delimiter
+fn delimited(input: &str) -> Result<(&str, &str), String> {
+    let delimiter = input.chars().next().ok_or("missing delimited value")?;
+    let body = &input[delimiter.len_utf8()..];
+    let mut escaped = false;
+    for (index, c) in body.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == delimiter => return Ok((&body[..index], &body[index + c.len_utf8()..])),
+            _ => {}
+        }
+    }
+    Err(format!("missing closing `{}`", delimiter))
+}
+
+fn unescape(input: &str) -> Result<Vec<u8>, String> {
+    let mut out = vec![];
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('r') => out.push(b'\r'),
+            Some('n') => out.push(b'\n'),
+            Some('t') => out.push(b'\t'),
+            Some('0') => out.push(0),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&hex, 16)
+                    .map_err(|_| format!("invalid escape `\\x{}`", hex))?;
+                out.push(byte);
+            }
+            Some(c) => {
+                let mut buf = [0; 4];
+                out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            None => return Err("dangling `\\` at end of payload".into()),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_signatures_parse() {
+        assert!(!SignatureDb::builtin().probes().is_empty());
+    }
+
+    #[test]
+    fn parses_probes_and_matches() {
+        let db = SignatureDb::parse(
+            "# in-house protocol\n\
+             probe hello q|HELLO\\r\\n|\n\
+             match acme m|^ACME/([\\d.]+)|i p/Acme server/ v/$1/\n",
+        )
+        .unwrap();
+        assert_eq!(db.probes()[0].payload(), b"HELLO\r\n");
+        let detection = db.identify(None, b"acme/2.1 ready").unwrap();
+        assert_eq!(detection.service.as_deref(), Some("acme"));
+        assert_eq!(detection.product.as_deref(), Some("Acme server"));
+        assert_eq!(detection.version.as_deref(), Some("2.1"));
+    }
+
+    #[test]
+    fn rejects_invalid_regex() {
+        let err = SignatureDb::parse("probe banner\nmatch x m|(unclosed|").unwrap_err();
+        assert!(err.starts_with("line 2: "), "{}", err);
+    }
+
+    #[test]
+    fn rejects_unknown_field() {
+        assert_eq!(
+            SignatureDb::parse("probe banner\nmatch x m|x| d/device/").unwrap_err(),
+            "line 2: unknown field `d/device/`"
+        );
+    }
+
+    #[test]
+    fn rejects_empty_file() {
+        assert_eq!(SignatureDb::parse("").unwrap_err(), "no probes defined");
+        assert_eq!(
+            SignatureDb::parse("# only comments\n\n").unwrap_err(),
+            "no probes defined"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let error = |source| SignatureDb::parse(source).unwrap_err();
+        assert_eq!(
+            error("match x m|x|"),
+            "line 1: `match` must follow a `probe` line"
+        );
+        assert_eq!(error("probe"), "line 1: probe name is missing");
+        assert_eq!(error("probe p q|open"), "line 1: missing closing `|`");
+        assert_eq!(error("probe p q|\\xzz|"), "line 1: invalid escape `\\xzz`");
+        assert_eq!(error("expect x"), "line 1: unknown directive `expect`");
+    }
+}
@@ -0,0 +1,656 @@
+use std::{
+    io::ErrorKind,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use nu_protocol::{Record, Span, Value, record};
+
+use crate::helpers::AsValue;
+
+use super::{
+    connection::{self, Connection, Session, TlsTarget},
+    scan_config::ScanConfig,
+};
+
+const MAX_REDIRECTS: usize = 10;
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+/// `--http` speaks HTTP/1.1 only, h2 must not be negotiated
+pub(super) const HTTP_ALPN: &[&str] = &["http/1.1"];
+
+#[derive(Default, Clone, Debug)]
+struct HttpTimings {
+    connect: Duration,
+    tls: Option<Duration>,
+    /// from the moment the request was sent until the first byte of the response
+    first_byte: Option<Duration>,
+    total: Duration,
+}
+
+impl AsValue for HttpTimings {
+    fn as_value(self, span: Span) -> Value {
+        record! {
+            "connect" => self.connect.as_value(span),
+            "tls" => self.tls.as_value(span),
+            "first_byte" => self.first_byte.as_value(span),
+            "total" => self.total.as_value(span),
+        }
+        .as_value(span)
+    }
+}
+
+/// Response of the last request made by `--http` (after following redirects)
+#[derive(Default, Clone, Debug)]
+pub(super) struct HttpReport {
+    url: String,
+    status: Option<u16>,
+    reason: Option<String>,
+    version: Option<String>,
+    headers: Record,
+    body: Option<Vec<u8>>,
+    body_truncated: bool,
+    redirects: Vec<String>,
+    error: Option<String>,
+    timings: HttpTimings,
+}
+
+impl AsValue for HttpReport {
+    fn as_value(self, span: Span) -> Value {
+        let body = self.body.map(|body| match String::from_utf8(body) {
+            Ok(text) => Value::string(text, span),
+            Err(err) => Value::binary(err.into_bytes(), span),
+        });
+        record! {
+            "url" => self.url.as_value(span),
+            "status" => self.status.as_value(span),
+            "reason" => self.reason.as_value(span),
+            "version" => self.version.as_value(span),
+            "headers" => self.headers.as_value(span),
+            "body" => body.as_value(span),
+            "body_truncated" => self.body_truncated.as_value(span),
+            "redirects" => self.redirects.as_value(span),
+            "error" => self.error.as_value(span),
+            "timings" => self.timings.as_value(span),
+        }
+        .as_value(span)
+    }
+}
+
+/// Where the next request goes
+struct Location {
+    address: SocketAddr,
+    host: String,
+    path: String,
+    tls: bool,
+}
+
+impl Location {
+    fn url(&self) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        let default_port = if self.tls { 443 } else { 80 };
+        match self.address.port() == default_port {
+            true => format!("{}://{}{}", scheme, self.host, self.path),
+            false => format!(
+                "{}://{}:{}{}",
+                scheme,
+                self.host,
+                self.address.port(),
+                self.path
+            ),
+        }
+    }
+
+    /// Resolves a `Location` header relative to this location
+    fn follow(&self, location: &str) -> Result<Location, String> {
+        let (tls, rest) = match location.split_once("://") {
+            Some(("http", rest)) => (false, rest),
+            Some(("https", rest)) => (true, rest),
+            Some((scheme, _)) => {
+                return Err(format!("cannot follow redirect to `{}` scheme", scheme));
+            }
+            None if location.starts_with('/') => {
+                return Ok(Location {
+                    path: location.to_string(),
+                    host: self.host.clone(),
+                    ..*self
+                });
+            }
+            None => {
+                let directory = &self.path[..self.path.rfind('/').map_or(0, |i| i + 1)];
+                return Ok(Location {
+                    path: format!("/{}{}", directory.trim_start_matches('/'), location),
+                    host: self.host.clone(),
+                    ..*self
+                });
+            }
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let default_port = if tls { 443 } else { 80 };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !host.ends_with(':') && port.parse::<u16>().is_ok() => {
+                (host, port.parse::<u16>().unwrap())
+            }
+            _ => (authority, default_port),
+        };
+        let address = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, port),
+            Err(_) if host == self.host => SocketAddr::new(self.address.ip(), port),
+            Err(_) => (host, port)
+                .to_socket_addrs()
+                .map_err(|e| format!("cannot resolve `{}`: {}", host, e))?
+                .next()
+                .ok_or_else(|| format!("`{}` has no address", host))?,
+        };
+        Ok(Location {
+            address,
+            host: host.to_string(),
+            path: path.to_string(),
+            tls,
+        })
+    }
+
+    fn sni(&self) -> Option<&str> {
+        match self.host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            Ok(_) => None,
+            Err(_) => Some(&self.host),
+        }
+    }
+}
+
+/// Sends `GET path` over the already opened session and follows redirects when requested
+pub(super) fn probe(
+    address: SocketAddr,
+    cfg: &ScanConfig,
+    path: &str,
+    session: Session,
+) -> HttpReport {
+    let started = Instant::now();
+    let elapsed_before = session.connect_time + session.tls_time.unwrap_or_default();
+    let mut location = Location {
+        address,
+        host: cfg.sni().clone().unwrap_or_else(|| match address.ip() {
+            IpAddr::V4(ip) => ip.to_string(),
+            IpAddr::V6(ip) => format!("[{}]", ip),
+        }),
+        path: match path.starts_with('/') {
+            true => path.to_string(),
+            false => format!("/{}", path),
+        },
+        tls: *cfg.tls(),
+    };
+    let mut session = Some(session);
+    let mut report = HttpReport::default();
+    loop {
+        report.url = location.url();
+        let current = match session.take() {
+            Some(session) => Ok(session),
+            None => {
+                let tls = location.tls.then(|| TlsTarget {
+                    sni: location.sni(),
+                    starttls: None,
+                    alpn: HTTP_ALPN,
+                });
+                connection::open(location.address, cfg, tls).map_err(|e| e.to_string())
+            }
+        };
+        let current = match current {
+            Ok(current) => current,
+            Err(err) => {
+                report.error = Some(err);
+                break;
+            }
+        };
+        report.timings.connect = current.connect_time;
+        report.timings.tls = current.tls_time;
+        let Some(mut connection) = current.connection else {
+            report.error = current
+                .tls
+                .and_then(|tls| tls.error().map(str::to_string))
+                .map(|err| format!("tls handshake failed: {}", err));
+            break;
+        };
+        let exchange_started = Instant::now();
+        let response = exchange(
+            connection.as_mut(),
+            &location.host,
+            &location.path,
+            *cfg.http_body_bytes(),
+        );
+        let (response, first_byte) = match response {
+            Ok(response) => response,
+            Err(err) => {
+                report.error = Some(err);
+                break;
+            }
+        };
+        report.timings.first_byte = first_byte.map(|t| t - exchange_started);
+        let redirect = response
+            .headers
+            .get("location")
+            .and_then(|value| value.as_str().ok())
+            .map(str::to_string);
+        report.status = Some(response.status);
+        report.reason = Some(response.reason);
+        report.version = Some(response.version);
+        report.headers = response.headers;
+        report.body = Some(response.body);
+        report.body_truncated = response.body_truncated;
+        if let Some(err) = response.error {
+            report.error = Some(err);
+            break;
+        }
+        match redirect {
+            Some(target)
+                if *cfg.follow_redirects()
+                    && (300..400).contains(&response.status)
+                    && report.redirects.len() < MAX_REDIRECTS =>
+            {
+                match location.follow(&target) {
+                    Ok(next) => {
+                        report.redirects.push(report.url.clone());
+                        location = next;
+                    }
+                    Err(err) => {
+                        report.error = Some(err);
+                        break;
+                    }
+                }
+            }
+            _ => break,
+        }
+    }
+    report.timings.total = started.elapsed() + elapsed_before;
+    report
+}
+
+struct Response {
+    version: String,
+    status: u16,
+    reason: String,
+    headers: Record,
+    body: Vec<u8>,
+    body_truncated: bool,
+    /// Why reading the body stopped early, the head was still received
+    error: Option<String>,
+}
+
+/// Writes the request and reads the response head plus at most `body_limit` body bytes,
+/// also returns when the first byte of the response arrived
+fn exchange(
+    connection: &mut dyn Connection,
+    host: &str,
+    path: &str,
+    body_limit: usize,
+) -> Result<(Response, Option<Instant>), String> {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: nu_plugin_port_extension/{}\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        path,
+        host,
+        env!("CARGO_PKG_VERSION")
+    );
+    connection
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("cannot send request: {}", e))?;
+
+    let mut buffer = vec![];
+    let mut first_byte = None;
+    let head_end = loop {
+        if let Some(index) = find(&buffer, b"\r\n\r\n") {
+            break index;
+        }
+        if buffer.len() > MAX_HEADER_SIZE {
+            return Err("response headers are too large".into());
+        }
+        match read_chunk(connection, &mut buffer)? {
+            0 => return Err("connection closed before the response headers were received".into()),
+            _ => {
+                first_byte.get_or_insert_with(Instant::now);
+            }
+        }
+    };
+    let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next().unwrap_or_default();
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts
+        .next()
+        .filter(|v| v.starts_with("HTTP/"))
+        .ok_or_else(|| format!("invalid status line `{}`", status_line))?;
+    let status = parts
+        .next()
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| format!("invalid status line `{}`", status_line))?;
+    let reason = parts.next().unwrap_or_default().to_string();
+
+    let span = Span::unknown();
+    let mut headers = Record::new();
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = name.trim().to_lowercase();
+        let value = value.trim();
+        // repeated headers are combined as described in RFC 9110
+        let combined = match headers.get(&name).and_then(|v| v.as_str().ok()) {
+            Some(previous) => format!("{}, {}", previous, value),
+            None => value.to_string(),
+        };
+        headers.insert(name, combined.as_value(span));
+    }
+    let header = |name: &str| headers.get(name).and_then(|v| v.as_str().ok());
+
+    let raw_body = buffer.split_off(head_end + 4);
+    let has_body = !(100..200).contains(&status) && status != 204 && status != 304;
+    let chunked =
+        header("transfer-encoding").is_some_and(|te| te.to_lowercase().contains("chunked"));
+    let content_length = header("content-length").and_then(|cl| cl.parse::<usize>().ok());
+    let (body, body_truncated, error) = match (has_body, chunked) {
+        (false, _) => (vec![], false, None),
+        (true, true) => read_chunked_body(connection, raw_body, body_limit),
+        (true, false) => read_body(connection, raw_body, content_length, body_limit),
+    };
+
+    Ok((
+        Response {
+            version: version.to_string(),
+            status,
+            reason,
+            headers,
+            body,
+            body_truncated,
+            error,
+        },
+        first_byte,
+    ))
+}
+
+/// Reads a body delimited by its length or the end of the connection, at most `limit` bytes are kept
+fn read_body(
+    connection: &mut dyn Connection,
+    mut raw: Vec<u8>,
+    length: Option<usize>,
+    limit: usize,
+) -> (Vec<u8>, bool, Option<String>) {
+    let wanted = length.unwrap_or(usize::MAX).min(limit.saturating_add(1));
+    let mut error = None;
+    while raw.len() < wanted {
+        match read_chunk(connection, &mut raw) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err) => {
+                error = Some(err);
+                break;
+            }
+        }
+    }
+    let truncated = raw.len() > limit
+        || length.is_some_and(|length| length > limit.min(raw.len()))
+        || error.is_some();
+    raw.truncate(limit);
+    (raw, truncated, error)
+}
+
+/// Reads a chunked body until the last chunk or `limit` decoded bytes
+fn read_chunked_body(
+    connection: &mut dyn Connection,
+    raw: Vec<u8>,
+    limit: usize,
+) -> (Vec<u8>, bool, Option<String>) {
+    let mut decoder = ChunkedDecoder::default();
+    let mut body = vec![];
+    let mut raw = raw;
+    let error = loop {
+        if let Err(err) = decoder.feed(&raw, &mut body) {
+            break Some(err);
+        }
+        if decoder.complete || body.len() > limit {
+            break None;
+        }
+        raw.clear();
+        match read_chunk(connection, &mut raw) {
+            // closed before the last chunk, the body is incomplete
+            Ok(0) => break None,
+            Ok(_) => {}
+            Err(err) => break Some(err),
+        }
+    };
+    let truncated = body.len() > limit || !decoder.complete || error.is_some();
+    body.truncate(limit);
+    (body, truncated, error)
+}
+
+/// Appends whatever is available to `buffer`, returns 0 once the peer closed the connection
+fn read_chunk(connection: &mut dyn Connection, buffer: &mut Vec<u8>) -> Result<usize, String> {
+    let mut chunk = [0; 8192];
+    match connection.read(&mut chunk) {
+        Ok(size) => {
+            buffer.extend_from_slice(&chunk[..size]);
+            Ok(size)
+        }
+        // TLS peers often close without close_notify once the response is sent
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(0),
+        Err(err) => Err(format!("cannot read response: {}", err)),
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Longest chunk size line accepted, extensions included
+const MAX_CHUNK_LINE: usize = 4096;
+
+/// Decodes a chunked body as it arrives, each byte is looked at once
+#[derive(Default, Debug)]
+struct ChunkedDecoder {
+    /// Start of a size line that did not fully arrive yet
+    line: Vec<u8>,
+    /// Data bytes of the current chunk still to come
+    remaining: usize,
+    /// Bytes of the CRLF after the chunk data still to skip
+    skip: usize,
+    /// The last chunk was seen
+    complete: bool,
+}
+
+impl ChunkedDecoder {
+    /// Appends the data of `raw` to `body`
+    fn feed(&mut self, mut raw: &[u8], body: &mut Vec<u8>) -> Result<(), String> {
+        while !raw.is_empty() && !self.complete {
+            if self.remaining > 0 {
+                let size = self.remaining.min(raw.len());
+                body.extend_from_slice(&raw[..size]);
+                raw = &raw[size..];
+                self.remaining -= size;
+                if self.remaining == 0 {
+                    self.skip = 2;
+                }
+                continue;
+            }
+            if self.skip > 0 {
+                let size = self.skip.min(raw.len());
+                raw = &raw[size..];
+                self.skip -= size;
+                continue;
+            }
+            let Some(line_end) = find(raw, b"\n") else {
+                if self.line.len() + raw.len() > MAX_CHUNK_LINE {
+                    return Err("chunk size line is too long".into());
+                }
+                self.line.extend_from_slice(raw);
+                return Ok(());
+            };
+            self.line.extend_from_slice(&raw[..line_end]);
+            raw = &raw[line_end + 1..];
+            let line = std::mem::take(&mut self.line);
+            let line = String::from_utf8_lossy(&line);
+            let size_field = line.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size_field, 16)
+                .map_err(|_| format!("invalid chunk size `{}`", line.trim_end()))?;
+            match size {
+                0 => self.complete = true,
+                size => self.remaining = size,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        io::{self, Read, Write},
+    };
+
+    use super::*;
+
+    /// Feeds all of `raw` at once, returns the body and whether the last chunk was seen
+    fn decode_chunked(raw: &[u8]) -> Result<(Vec<u8>, bool), String> {
+        let mut decoder = ChunkedDecoder::default();
+        let mut body = vec![];
+        decoder.feed(raw, &mut body)?;
+        Ok((body, decoder.complete))
+    }
+
+    /// Connection replaying scripted reads
+    struct Scripted(VecDeque<io::Result<Vec<u8>>>);
+
+    impl Read for Scripted {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                Some(Ok(data)) => {
+                    buf[..data.len()].copy_from_slice(&data);
+                    Ok(data.len())
+                }
+                Some(Err(err)) => Err(err),
+                None => Ok(0),
+            }
+        }
+    }
+
+    impl Write for Scripted {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Connection for Scripted {
+        fn set_read_timeout(&self, _: Option<Duration>) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn exchange_with(reads: Vec<io::Result<&[u8]>>, body_limit: usize) -> Response {
+        let mut connection = Scripted(
+            reads
+                .into_iter()
+                .map(|read| read.map(<[u8]>::to_vec))
+                .collect(),
+        );
+        exchange(&mut connection, "localhost", "/", body_limit)
+            .unwrap()
+            .0
+    }
+
+    const CHUNKED_HEAD: &[u8] = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+
+    #[test]
+    fn decodes_complete_chunked_body() {
+        let raw = b"5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\n\r\n";
+        assert_eq!(decode_chunked(raw), Ok((b"hello world".to_vec(), true)));
+    }
+
+    #[test]
+    fn decodes_byte_by_byte() {
+        let raw = b"5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\n\r\n";
+        let mut decoder = ChunkedDecoder::default();
+        let mut body = vec![];
+        for byte in raw.chunks(1) {
+            decoder.feed(byte, &mut body).unwrap();
+        }
+        assert_eq!(body, b"hello world");
+        assert!(decoder.complete);
+    }
+
+    #[test]
+    fn keeps_partial_chunk_until_more_data_arrives() {
+        assert_eq!(
+            decode_chunked(b"5\r\nhello\r\na\r\nwor"),
+            Ok((b"hellowor".to_vec(), false))
+        );
+        assert_eq!(decode_chunked(b"5\r\nhel"), Ok((b"hel".to_vec(), false)));
+    }
+
+    #[test]
+    fn huge_chunk_size_does_not_overflow() {
+        let raw = b"ffffffffffffffff\r\nabc";
+        assert_eq!(decode_chunked(raw), Ok((b"abc".to_vec(), false)));
+    }
+
+    #[test]
+    fn invalid_chunk_size_is_an_error() {
+        assert!(decode_chunked(b"5\r\nhello\r\nzz\r\nmore").is_err());
+        assert!(decode_chunked(b"1ffffffffffffffff\r\n").is_err());
+        assert_eq!(
+            decode_chunked(&[b'1'; MAX_CHUNK_LINE + 1]),
+            Err("chunk size line is too long".to_string())
+        );
+    }
+
+    #[test]
+    fn chunked_body_stops_at_the_limit() {
+        // an endless body is cut once the limit is passed
+        let chunk = b"1000\r\n".iter().chain(&[b'x'; 0x1000]).chain(b"\r\n");
+        let chunk: Vec<u8> = chunk.copied().collect();
+        let mut reads = vec![Ok(CHUNKED_HEAD)];
+        reads.extend((0..100).map(|_| Ok(chunk.as_slice())));
+        let response = exchange_with(reads, 10_000);
+        assert_eq!(response.body.len(), 10_000);
+        assert!(response.body_truncated);
+        assert_eq!(response.error, None);
+    }
+
+    #[test]
+    fn read_error_keeps_the_head() {
+        let reads = vec![
+            Ok(CHUNKED_HEAD),
+            Ok(&b"5\r\nhello\r\n"[..]),
+            Err(io::Error::from(ErrorKind::ConnectionReset)),
+        ];
+        let response = exchange_with(reads, 1024);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"hello");
+        assert!(response.body_truncated);
+        assert!(response.error.unwrap().starts_with("cannot read response"));
+    }
+
+    #[test]
+    fn invalid_chunk_keeps_the_head() {
+        let reads = vec![Ok(CHUNKED_HEAD), Ok(&b"5\r\nhello\r\nzz\r\n"[..])];
+        let response = exchange_with(reads, 1024);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"hello");
+        assert_eq!(response.error.as_deref(), Some("invalid chunk size `zz`"));
+    }
+
+    #[test]
+    fn content_length_body_is_read_fully() {
+        let reads = vec![
+            Ok(&b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello"[..]),
+            Ok(b"world"),
+        ];
+        let response = exchange_with(reads, 1024);
+        assert_eq!(response.body, b"helloworld");
+        assert!(!response.body_truncated);
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    cell::Cell,
+    io::{self, ErrorKind, Read, Write},
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+use nu_protocol::Signals;
+use socket2::{SockAddr, Socket};
+
+/// Longest a blocking wait goes without looking for ctrl-c
+pub(super) const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Engine signals, looked at by every blocking step of a scan
+#[derive(Clone, Debug)]
+pub(crate) struct Interrupt(Signals);
+
+impl Default for Interrupt {
+    fn default() -> Self {
+        Self(Signals::empty())
+    }
+}
+
+impl Interrupt {
+    pub fn new(signals: Signals) -> Self {
+        Self(signals)
+    }
+
+    pub fn triggered(&self) -> bool {
+        self.0.interrupted()
+    }
+
+    /// Fails once ctrl-c was pressed. The error is not `ErrorKind::Interrupted`,
+    /// which `read_exact` and `write_all` would silently retry.
+    pub fn check(&self) -> io::Result<()> {
+        match self.triggered() {
+            true => Err(io::Error::other("interrupted by user")),
+            false => Ok(()),
+        }
+    }
+
+    /// Sleeps for `duration` unless interrupted before
+    pub fn sleep(&self, duration: Duration) -> io::Result<()> {
+        let deadline = Instant::now() + duration;
+        loop {
+            self.check()?;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(());
+            }
+            thread::sleep(remaining.min(CHECK_INTERVAL));
+        }
+    }
+
+    /// Connects `socket` without blocking for longer than `CHECK_INTERVAL` at a time,
+    /// an interrupted connect is dropped with the socket
+    pub fn connect(
+        &self,
+        socket: &Socket,
+        address: &SockAddr,
+        timeout: Duration,
+    ) -> io::Result<()> {
+        self.check()?;
+        let deadline = Instant::now() + timeout;
+        socket.set_nonblocking(true)?;
+        match socket.connect(address) {
+            Ok(()) => return socket.set_nonblocking(false),
+            Err(err) if in_progress(&err) => {}
+            Err(err) => return Err(err),
+        }
+        loop {
+            self.check()?;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(ErrorKind::TimedOut.into());
+            }
+            if poll_connected(socket, remaining.min(CHECK_INTERVAL))? {
+                return socket.set_nonblocking(false);
+            }
+        }
+    }
+
+    /// Runs `read` with a read timeout of at most `CHECK_INTERVAL` until it returns data
+    /// or `timeout` is over. `set_timeout` applies the timeout of one slice to the socket.
+    pub fn read<T>(
+        &self,
+        timeout: Option<Duration>,
+        mut set_timeout: impl FnMut(Duration) -> io::Result<()>,
+        mut read: impl FnMut() -> io::Result<T>,
+    ) -> io::Result<T> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            self.check()?;
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let slice = remaining.map_or(CHECK_INTERVAL, |remaining| remaining.min(CHECK_INTERVAL));
+            if slice.is_zero() {
+                return Err(io::Error::from(ErrorKind::WouldBlock));
+            }
+            set_timeout(slice)?;
+            match read() {
+                // the last slice reports the timeout as the socket did
+                Err(err)
+                    if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+                        && remaining.is_none_or(|remaining| remaining > slice) =>
+                {
+                    continue;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn in_progress(err: &io::Error) -> bool {
+    #[cfg(unix)]
+    if err.raw_os_error() == Some(libc::EINPROGRESS) {
+        return true;
+    }
+    err.kind() == ErrorKind::WouldBlock
+}
+
+/// Waits up to `timeout` for a connecting socket to become writable, `false` when it did not.
+/// A failed connect is returned as its socket error.
+#[cfg(unix)]
+fn poll_connected(socket: &Socket, timeout: Duration) -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    let mut pollfd = libc::pollfd {
+        fd: socket.as_raw_fd(),
+        events: libc::POLLOUT,
+        revents: 0,
+    };
+    let millis = timeout.as_millis().clamp(1, libc::c_int::MAX as u128) as libc::c_int;
+    // SAFETY: `pollfd` is a valid array of one entry for the duration of the call
+    match unsafe { libc::poll(&mut pollfd, 1, millis) } {
+        -1 => match io::Error::last_os_error() {
+            err if err.kind() == ErrorKind::Interrupted => Ok(false),
+            err => Err(err),
+        },
+        0 => Ok(false),
+        _ if pollfd.revents & (libc::POLLERR | libc::POLLHUP) != 0 => Err(socket_error(socket)),
+        _ => connected(socket),
+    }
+}
+
+/// Waits up to `timeout` for a connecting socket to become writable, `false` when it did not.
+/// A failed connect is returned as its socket error.
+#[cfg(windows)]
+fn poll_connected(socket: &Socket, timeout: Duration) -> io::Result<bool> {
+    use std::os::windows::io::AsRawSocket;
+    use windows_sys::Win32::Networking::WinSock::{
+        POLLERR, POLLHUP, POLLWRNORM, SOCKET_ERROR, WSAPOLLFD, WSAPoll,
+    };
+
+    let mut pollfd = WSAPOLLFD {
+        fd: socket.as_raw_socket() as _,
+        events: POLLWRNORM as i16,
+        revents: 0,
+    };
+    let millis = timeout.as_millis().clamp(1, i32::MAX as u128) as i32;
+    // SAFETY: `pollfd` is a valid array of one entry for the duration of the call
+    match unsafe { WSAPoll(&mut pollfd, 1, millis) } {
+        SOCKET_ERROR => Err(io::Error::last_os_error()),
+        0 => Ok(false),
+        _ if pollfd.revents & (POLLERR | POLLHUP) as i16 != 0 => Err(socket_error(socket)),
+        _ => connected(socket),
+    }
+}
+
+/// A writable socket may still carry the error of a failed connect
+fn connected(socket: &Socket) -> io::Result<bool> {
+    match socket.take_error()? {
+        Some(err) => Err(err),
+        None => Ok(true),
+    }
+}
+
+fn socket_error(socket: &Socket) -> io::Error {
+    match socket.take_error() {
+        Ok(Some(err)) | Err(err) => err,
+        Ok(None) => io::Error::other("connect failed without a socket error"),
+    }
+}
+
+/// Tcp stream whose blocking reads wake up regularly to look for ctrl-c
+#[derive(Debug)]
+pub(super) struct SignalStream {
+    stream: TcpStream,
+    interrupt: Interrupt,
+    read_timeout: Cell<Option<Duration>>,
+}
+
+impl SignalStream {
+    pub fn new(stream: TcpStream, interrupt: Interrupt) -> Self {
+        Self {
+            stream,
+            interrupt,
+            read_timeout: Cell::new(None),
+        }
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        if timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "cannot set a 0 duration timeout",
+            ));
+        }
+        self.read_timeout.set(timeout);
+        Ok(())
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_write_timeout(timeout)
+    }
+}
+
+impl Read for SignalStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let stream = &self.stream;
+        self.interrupt.read(
+            self.read_timeout.get(),
+            |slice| stream.set_read_timeout(Some(slice)),
+            || (&*stream).read(buf),
+        )
+    }
+}
+
+impl Write for SignalStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.interrupt.check()?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{SocketAddr, TcpListener},
+        sync::{Arc, atomic::AtomicBool},
+    };
+
+    use socket2::{Domain, Type};
+
+    use super::*;
+
+    fn tcp_socket() -> Socket {
+        Socket::new(Domain::IPV4, Type::STREAM, None).unwrap()
+    }
+
+    #[test]
+    fn connects_to_a_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = SockAddr::from(listener.local_addr().unwrap());
+        let socket = tcp_socket();
+        Interrupt::default()
+            .connect(&socket, &address, Duration::from_secs(5))
+            .unwrap();
+        assert!(socket.peer_addr().is_ok());
+        assert!(!socket.nonblocking().unwrap());
+    }
+
+    #[test]
+    fn reports_refused_connects() {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let err = Interrupt::default()
+            .connect(
+                &tcp_socket(),
+                &SockAddr::from(address),
+                Duration::from_secs(5),
+            )
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn interrupted_connect_fails() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address: SocketAddr = listener.local_addr().unwrap();
+        let interrupt = Interrupt::new(Signals::new(Arc::new(AtomicBool::new(true))));
+        let err = interrupt
+            .connect(
+                &tcp_socket(),
+                &SockAddr::from(address),
+                Duration::from_secs(5),
+            )
+            .unwrap_err();
+        assert_eq!(err.to_string(), "interrupted by user");
+    }
+}
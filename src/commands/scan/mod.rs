@@ -1,5 +1,27 @@
+mod check;
+mod connection;
+mod detect;
+mod http;
+mod interrupt;
+pub(crate) use interrupt::Interrupt;
+mod pacing;
 mod plugin;
+mod presets;
+mod probe_error;
+mod reachability;
+pub(crate) use reachability::{Answer, Reachability, Reply};
+mod receive;
+mod retry;
+mod rtt;
 pub(crate) use plugin::PortScan;
+#[allow(clippy::module_inception)]
 mod scan;
 mod scan_config;
 mod scan_result;
+mod scheduler;
+mod script;
+mod socket;
+mod target;
+mod timing;
+mod tls;
+mod udp_probe;
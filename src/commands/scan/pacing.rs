@@ -0,0 +1,136 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::interrupt::Interrupt;
+
+/// Spacing of the probes of one `port scan` call, shared by every target it scans
+#[derive(Default, Clone, Debug)]
+pub(super) struct Pacing {
+    limiter: Option<Arc<RateLimiter>>,
+    /// Random extra pause of up to this long before each probe
+    jitter: Option<Duration>,
+}
+
+impl Pacing {
+    pub fn new(rate: Option<f64>, jitter: Option<Duration>) -> Self {
+        Self {
+            limiter: rate.map(|rate| Arc::new(RateLimiter::new(rate))),
+            jitter,
+        }
+    }
+
+    /// Blocks until the next probe may be sent, fails when interrupted meanwhile
+    pub fn wait(&self, interrupt: &Interrupt) -> io::Result<()> {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire(interrupt)?;
+        }
+        if let Some(jitter) = self.jitter.filter(|jitter| !jitter.is_zero()) {
+            interrupt.sleep(rand::random_range(Duration::ZERO..=jitter))?;
+        }
+        Ok(())
+    }
+}
+
+/// Token bucket holding a single token, so probes are spread evenly instead of sent in bursts
+#[derive(Debug)]
+struct RateLimiter {
+    /// Probes per second
+    rate: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: 1.0,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    fn acquire(&self, interrupt: &Interrupt) -> io::Result<()> {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let refill = now.duration_since(bucket.refilled).as_secs_f64() * self.rate;
+                bucket.tokens = (bucket.tokens + refill).min(1.0);
+                bucket.refilled = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return Ok(());
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate)
+            };
+            interrupt.sleep(wait)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn without_a_rate_probes_are_not_delayed() {
+        let started = Instant::now();
+        for _ in 0..100 {
+            Pacing::default().wait(&Interrupt::default()).unwrap();
+        }
+        assert!(started.elapsed() < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn rate_spaces_the_probes() {
+        let pacing = Pacing::new(Some(20.0), None);
+        let interrupt = Interrupt::default();
+        let started = Instant::now();
+        // the first token is there right away, the next four take 50ms each
+        for _ in 0..5 {
+            pacing.wait(&interrupt).unwrap();
+        }
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(195), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(500), "{elapsed:?}");
+    }
+
+    #[test]
+    fn rate_is_shared_by_clones() {
+        let pacing = Pacing::new(Some(20.0), None);
+        let started = Instant::now();
+        let threads = (0..4)
+            .map(|_| {
+                let pacing = pacing.clone();
+                std::thread::spawn(move || pacing.wait(&Interrupt::default()).unwrap())
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(145), "{elapsed:?}");
+    }
+
+    #[test]
+    fn idle_time_does_not_build_a_burst() {
+        let limiter = RateLimiter::new(20.0);
+        let interrupt = Interrupt::default();
+        std::thread::sleep(Duration::from_millis(200));
+        let started = Instant::now();
+        limiter.acquire(&interrupt).unwrap();
+        limiter.acquire(&interrupt).unwrap();
+        // the bucket holds one token, a long pause saves up no more than that
+        assert!(started.elapsed() >= Duration::from_millis(45));
+    }
+}
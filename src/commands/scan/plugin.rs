@@ -1,13 +1,20 @@
 use crate::PortExtension;
-use crate::commands::scan::scan_result::ScanResultBuilder;
+use crate::commands::scan::scan_result::{PortState, ScanResultBuilder};
 use crate::helpers::AsValue;
 use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
-use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, Span, SyntaxShape};
+use nu_protocol::{
+    Category, Example, LabeledError, ListStream, PipelineData, Record, ShellError, Signature, Span,
+    SyntaxShape, Value,
+};
 use std::time::Duration;
 use std::vec;
 
+use super::check;
+use super::presets;
 use super::scan;
 use super::scan_config::ScanConfig;
+use super::scheduler::{self, Job};
+use super::target::Target;
 
 #[derive(Default)]
 pub struct PortScan {}
@@ -26,12 +33,12 @@ impl PluginCommand for PortScan {
     }
     fn signature(&self) -> Signature {
         Signature::build("port scan")
-        .required(
+        .optional(
         "target IP",
         SyntaxShape::String,
-        "target IP address to check for open port",
+        "target IP address to check for open port (or the address used by piped rows without one)",
         )
-        .required("port", SyntaxShape::Int, "port to be checked")
+        .optional("port", SyntaxShape::Int, "port to be checked (or the port used by piped rows without one)")
         .named(
         "timeout",
         SyntaxShape::Duration,
@@ -47,10 +54,155 @@ impl PluginCommand for PortScan {
         .named(
         "receive-byte-count",
          SyntaxShape::OneOf(vec![SyntaxShape::Filesize,SyntaxShape::Int]),
-         "bytes to receive from the target (possibly after sending the `send` data) to mark the connection as open", 
+         "bytes to receive from the target (possibly after sending the `send` data) to mark the connection as open, also caps the read conditions below (default cap: 64KiB)", 
          Some('b'),
         )
-        .switch("udp", "udp scan mod (send and receive-byte-count flags will be mandatory due to how udp works)", Some('u'))
+        .named(
+        "read-until",
+        SyntaxShape::OneOf(vec![SyntaxShape::String,SyntaxShape::Binary]),
+        "stop reading once the response contains this delimiter",
+        None,
+        )
+        .named(
+        "read-regex",
+        SyntaxShape::String,
+        "stop reading once the response matches this regex",
+        None,
+        )
+        .named(
+        "read-idle",
+        SyntaxShape::Duration,
+        "stop reading once the target stayed quiet for this long",
+        None,
+        )
+        .switch("read-to-eof", "read until the target closes the connection", None)
+        .named(
+        "script",
+        SyntaxShape::List(Box::new(SyntaxShape::Record(vec![]))),
+        "send/expect dialogue run over the connection, a list of `{send?, expect?, timeout?}` records; the first unmet expectation fails the script (see the `script` column)",
+        None,
+        )
+        .switch("udp", "udp scan mode (without `send` an empty datagram, or a default request for well-known ports, is sent)", Some('u'))
+        .named(
+        "probe",
+        SyntaxShape::String,
+        "udp protocol probe generating the request and parsing the reply into `probe_result` (dns, ntp, snmp, ssdp, stun, memcached)",
+        Some('p'),
+        )
+        .named(
+        "check",
+        SyntaxShape::Closure(Some(vec![SyntaxShape::Record(vec![])])),
+        "closure validating the response of a reachable port, it gets the scan result (received data, elapsed, ...) and returns a bool deciding `is_open` or a record merged into the result",
+        Some('c'),
+        )
+        .switch("tls", "perform a TLS handshake after connecting and report the negotiated parameters and certificate chain in a `tls` column", None)
+        .named(
+        "sni",
+        SyntaxShape::String,
+        "server name sent during the TLS handshake (implies `--tls`)",
+        None,
+        )
+        .named(
+        "starttls",
+        SyntaxShape::String,
+        "upgrade a plain text protocol before the TLS handshake: smtp, imap, pop3, ftp or postgres (implies `--tls`)",
+        None,
+        )
+        .named(
+        "http",
+        SyntaxShape::String,
+        "send an HTTP/1.1 GET request for the given path (over TLS with `--tls`) and report the response in an `http` column",
+        None,
+        )
+        .named(
+        "http-body-bytes",
+        SyntaxShape::OneOf(vec![SyntaxShape::Filesize,SyntaxShape::Int]),
+        "amount of the response body captured by `--http` (default: 1KiB)",
+        None,
+        )
+        .switch("follow-redirects", "follow redirects returned to `--http` requests (up to 10)", None)
+        .named(
+        "retries",
+        SyntaxShape::Int,
+        "repeat the probe up to this many times when it times out, fails in transit or (udp) gets no answer; adds `attempts` and `attempt_elapsed` columns",
+        Some('r'),
+        )
+        .named(
+        "backoff",
+        SyntaxShape::Duration,
+        "pause between retries (default: 100ms)",
+        None,
+        )
+        .switch("backoff-exponential", "double the pause after every retry", None)
+        .switch("backoff-jitter", "pick each pause at random between half and all of its length", None)
+        .named(
+        "bind",
+        SyntaxShape::String,
+        "local address (`ip` or `ip:port`) the probe is sent from",
+        None,
+        )
+        .named(
+        "interface",
+        SyntaxShape::String,
+        "network device the probe socket is bound to (SO_BINDTODEVICE, linux only)",
+        None,
+        )
+        .named("ttl", SyntaxShape::Int, "time to live (ipv4) or hop limit (ipv6) of the probe packets", None)
+        .named("tos", SyntaxShape::Int, "type of service (ipv4) or traffic class (ipv6) byte of the probe packets", None)
+        .named("dscp", SyntaxShape::Int, "differentiated services code point of the probe packets (the upper six bits of `--tos`)", None)
+        .switch("rst-close", "close tcp connections with a reset (SO_LINGER 0) instead of leaving TIME_WAIT sockets behind", None)
+        .named(
+        "rate",
+        SyntaxShape::Number,
+        "maximum number of probes per second, retries included",
+        None,
+        )
+        .named(
+        "parallel",
+        SyntaxShape::Int,
+        "number of piped targets scanned at the same time (default: 1)",
+        None,
+        )
+        .named(
+        "max-per-host",
+        SyntaxShape::Int,
+        "maximum number of piped targets of the same address scanned at the same time",
+        None,
+        )
+        .switch("randomize", "scan the piped targets in random order, each picked among the next 4096", None)
+        .named(
+        "jitter",
+        SyntaxShape::Duration,
+        "random pause of up to this long before each probe",
+        None,
+        )
+        .switch("adaptive", "derive per-host timeouts from the measured round-trip times (smoothed rtt + 4 × variation, doubled on every retry, bounded by the timing preset), `--timeout` only applies until a host answered; adds a `timeout` column", None)
+        .named(
+        "timing",
+        SyntaxShape::String,
+        "preset for timeout, rate and parallelism of flags that are not given: paranoid, polite, normal (default), aggressive or insane",
+        None,
+        )
+        .named(
+        "top-ports",
+        SyntaxShape::Int,
+        "scan the n most common tcp (or udp with `--udp`) ports of the target, at most 100 tcp or 50 udp",
+        None,
+        )
+        .named(
+        "profile",
+        SyntaxShape::String,
+        "scan a named group of ports: web, db, mail, remote-admin, k8s or one defined in `$env.config.plugins.port_extension.profiles`",
+        None,
+        )
+        .switch("strict", "fail with an error instead of reporting the port as closed when the probe cannot be set up locally (e.g. bind errors)", None)
+        .switch("detect", "identify the service behind an open tcp port using banners and protocol probes (adds service, product and version columns)", Some('d'))
+        .named(
+        "signatures",
+        SyntaxShape::Filepath,
+        "extra signature file used by `--detect`, tried before the built-in signatures",
+        None,
+        )
         .category(Category::Network)
     }
     fn description(&self) -> &str {
@@ -66,7 +218,7 @@ impl PluginCommand for PortScan {
                     ScanResultBuilder::default()
                         .address("8.8.8.8")
                         .port(53)
-                        .is_open(true)
+                        .state(PortState::Open)
                         .elapsed(Duration::from_millis(27))
                         .received_data(None)
                         .build()
@@ -81,9 +233,11 @@ impl PluginCommand for PortScan {
                     ScanResultBuilder::default()
                         .address("8.8.8.8")
                         .port(54)
-                        .is_open(false)
+                        .state(PortState::Closed)
                         .elapsed(Duration::from_secs(1))
                         .received_data(None)
+                        .error(Some("timed out: connection timed out".to_string()))
+                        .error_kind(Some("timeout".to_string()))
                         .build()
                         .unwrap()
                         .as_value(Span::unknown()),
@@ -96,7 +250,7 @@ impl PluginCommand for PortScan {
                     ScanResultBuilder::default()
                         .address("8.8.8.8")
                         .port(53)
-                        .is_open(true)
+                        .state(PortState::Open)
                         .elapsed(Duration::from_millis(27))
                         .received_data(Some(vec![
                             0, 0, 129, 128, 0, 1, 0, 1, 0, 0, 0, 0, 3, 119, 119, 119, 6, 103, 111,
@@ -108,6 +262,101 @@ impl PluginCommand for PortScan {
                         .as_value(Span::unknown()),
                 ),
             },
+            Example {
+                example: "port scan 127.0.0.1 5353 --udp -t 500ms",
+                description: "probe a udp port without any payload, closed ports answer with ICMP port-unreachable (`closed`), silent ones are reported as `open|filtered`",
+                result: None,
+            },
+            Example {
+                example: "port scan 8.8.8.8 53 --udp --probe dns | get probe_result.rcode",
+                description: "send a dns query to Google's public dns and read the response code from the parsed reply",
+                result: None,
+            },
+            Example {
+                example: "port scan 1.1.1.1 443 --sni one.one.one.one | get tls.certificates.0.days_until_expiry",
+                description: "check how many days are left before the certificate served for one.one.one.one expires",
+                result: None,
+            },
+            Example {
+                example: "port scan 10.0.0.5 25 --starttls smtp | get tls.version",
+                description: "upgrade an smtp session with STARTTLS and report the negotiated TLS version",
+                result: None,
+            },
+            Example {
+                example: "port scan 10.0.0.5 8080 --http /healthz | get http.status",
+                description: "check whether the health endpoint of a service answers with 200",
+                result: None,
+            },
+            Example {
+                example: "port scan 1.1.1.1 443 --sni one.one.one.one --http / --follow-redirects | get http.timings",
+                description: "fetch a page over https, following redirects, and show the connect, tls, time to first byte and total timings",
+                result: None,
+            },
+            Example {
+                example: "port scan 10.0.0.5 25 --read-until \"\\r\\n\" | get received_data | decode",
+                description: "read the first line of an smtp banner without waiting for the timeout",
+                result: None,
+            },
+            Example {
+                example: "port scan 127.0.0.1 6379 --script [{send: \"AUTH secret\\r\\n\", expect: \"+OK\"} {send: \"PING\\r\\n\", expect: \"+PONG\"}] | get script.passed",
+                description: "authenticate against redis and check that it answers PING",
+                result: None,
+            },
+            Example {
+                example: "port scan 127.0.0.1 22 -b 4 --check {|resp| ($resp.received_data | decode) == 'SSH-' }",
+                description: "only report port 22 as open when it answers with an ssh banner",
+                result: None,
+            },
+            Example {
+                example: "port scan 127.0.0.1 22 --detect",
+                description: "identify the service listening on port 22 (e.g. service: ssh, product: OpenSSH, version: 9.6p1)",
+                result: None,
+            },
+            Example {
+                example: "port scan 10.0.0.5 443 --retries 3 --backoff 200ms --backoff-exponential --backoff-jitter",
+                description: "tolerate up to three lost SYNs, waiting about 200ms, 400ms and 800ms between attempts",
+                result: None,
+            },
+            Example {
+                example: "port scan 10.0.0.5 443 --bind 192.168.1.10 --dscp 46 --rst-close",
+                description: "probe from a specific source address with expedited forwarding marking, resetting the connection afterwards",
+                result: None,
+            },
+            Example {
+                example: "open inventory.csv | port scan -t 500ms | where not is_open",
+                description: "scan every `{address, port, udp?, send?, timeout?}` row of an inventory, the row values override the flags and the other columns are kept",
+                result: None,
+            },
+            Example {
+                example: "open inventory.csv | port scan --parallel 32 --max-per-host 2 --rate 50 --randomize",
+                description: "scan an inventory in random order with 32 workers, at most 2 probes in flight per host and no more than 50 probes per second",
+                result: None,
+            },
+            Example {
+                example: "1..254 | each { |i| {address: $\"10.0.0.($i)\", port: 22} } | port scan --timing aggressive --adaptive",
+                description: "scan fast with 64 workers and timeouts learned from each host's round-trip time",
+                result: None,
+            },
+            Example {
+                example: "port scan 10.0.0.5 --top-ports 100 --parallel 16 | where is_open",
+                description: "find which of the 100 most common tcp ports are open",
+                result: None,
+            },
+            Example {
+                example: "port scan 10.0.0.5 --profile db",
+                description: "check the usual database ports (postgres, mysql, redis, mongodb, ...)",
+                result: None,
+            },
+            Example {
+                example: "port list -l -t | port scan",
+                description: "check that every local tcp listener accepts connections",
+                result: None,
+            },
+            Example {
+                example: "['10.0.0.5:22' '10.0.0.6:22'] | port scan",
+                description: "scan `host:port` strings",
+                result: None,
+            },
             Example {
                 example: "7880..8000 | each { |it| port scan 127.0.0.1 $it -t 1ms } | where is_open",
                 description: "This command will scan any port from 7880 to 8000 on localhost and return open ports in range",
@@ -119,11 +368,188 @@ impl PluginCommand for PortScan {
     fn run(
         &self,
         _plugin: &Self::Plugin,
-        _engine: &EngineInterface,
+        engine: &EngineInterface,
         call: &EvaluatedCall,
-        _input: PipelineData,
+        input: PipelineData,
     ) -> Result<PipelineData, LabeledError> {
-        scan::scan(ScanConfig::try_from(call)?)
-            .map(|r| PipelineData::Value(r.as_value(call.head), None))
+        let cfg = ScanConfig::try_from(call)?.with_signals(engine.signals().clone());
+        let head = call.head;
+        let ports = match (cfg.top_ports(), cfg.profile()) {
+            (Some(count), _) => Some(presets::top_ports(*count, *cfg.udp())),
+            (None, Some(profile)) => Some(presets::profile_ports(
+                &profile.item,
+                profile.span,
+                engine.get_plugin_config()?.as_ref(),
+            )?),
+            (None, None) => None,
+        };
+        if let Some(ports) = ports {
+            if !matches!(
+                input,
+                PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, ..)
+            ) || cfg.target_address().is_empty()
+                || call.positional.len() > 1
+            {
+                return Err(LabeledError::new("port presets scan a single host").with_label(
+                    "`--top-ports` and `--profile` need a target IP, without a port or piped targets",
+                    head,
+                ));
+            }
+            let rows = ports
+                .into_iter()
+                .map(move |port| Value::int(port.into(), head));
+            return Ok(scan_rows(engine, cfg, rows, head));
+        }
+        match input {
+            PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, ..) => {
+                if !cfg.has_target() {
+                    return Err(LabeledError::new("missing scan target").with_label(
+                        "pass a target IP and port, or pipe targets into `port scan`",
+                        head,
+                    ));
+                }
+                Ok(PipelineData::Value(scan_checked(engine, cfg, head)?, None))
+            }
+            PipelineData::Value(
+                row @ (Value::Record { .. } | Value::String { .. } | Value::Int { .. }),
+                ..,
+            ) => Ok(PipelineData::Value(
+                scan_job(engine, prepare_row(&cfg, row)?, head)?,
+                None,
+            )),
+            input => Ok(scan_rows(engine, cfg, input.into_iter(), head)),
+        }
+    }
+}
+
+/// Scans every row as its own target, following the schedule of the flags
+fn scan_rows(
+    engine: &EngineInterface,
+    cfg: ScanConfig,
+    rows: impl Iterator<Item = Value> + Send + 'static,
+    head: Span,
+) -> PipelineData {
+    let signals = engine.signals().clone();
+    let engine = engine.clone();
+    let schedule = *cfg.schedule();
+    let interrupt = cfg.interrupt().clone();
+    let jobs = rows.map(move |row| {
+        let span = row.span();
+        prepare_row(&cfg, row).map_err(|e| Value::error(ShellError::from(e), span))
+    });
+    let results = scheduler::run(schedule, interrupt, jobs, move |job| {
+        let span = job.span;
+        scan_job(&engine, job, head).unwrap_or_else(|e| Value::error(ShellError::from(e), span))
+    });
+    PipelineData::list_stream(ListStream::new(results, head, signals), None)
+}
+
+/// Applies the overrides of one pipeline row to the flags
+fn prepare_row(cfg: &ScanConfig, row: Value) -> Result<Job, LabeledError> {
+    let target = Target::try_from(row)?;
+    Ok(Job {
+        cfg: cfg.with_target(&target)?,
+        columns: target.columns,
+        span: target.span,
+    })
+}
+
+/// Scans one pipeline row, keeping its input columns
+fn scan_job(engine: &EngineInterface, job: Job, head: Span) -> Result<Value, LabeledError> {
+    let value = scan_checked(engine, job.cfg, head)?;
+    let mut result = value.into_record()?;
+    keep_columns(&mut result, job.columns);
+    Ok(Value::record(result, head))
+}
+
+/// Adds the input columns to the result. One named like a result column is kept with a
+/// `target_` prefix, unless it holds the same value (such as the scanned address and port).
+fn keep_columns(result: &mut Record, columns: Record) {
+    for (column, value) in columns {
+        let mut name = column;
+        while let Some(existing) = result.get(&name) {
+            if *existing == value {
+                break;
+            }
+            name = format!("target_{}", name);
+        }
+        if !result.contains(&name) {
+            result.insert(name, value);
+        }
+    }
+}
+
+/// Scans the target and runs the `--check` closure on the result
+fn scan_checked(
+    engine: &EngineInterface,
+    cfg: ScanConfig,
+    head: Span,
+) -> Result<Value, LabeledError> {
+    let check = cfg.check().to_owned();
+    let result = scan::scan(cfg)?;
+    let state = result.state();
+    let value = result.as_value(head);
+    // closed ports have nothing to validate
+    match check {
+        Some(check) if state != PortState::Closed => check::apply(engine, &check, value),
+        _ => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::record;
+
+    use super::*;
+
+    #[test]
+    fn input_columns_are_kept() {
+        let mut result = record! {
+            "address" => Value::test_string("10.0.0.5"),
+            "port" => Value::test_int(22),
+            "is_open" => Value::test_bool(true),
+        };
+        keep_columns(
+            &mut result,
+            record! {
+                "address" => Value::test_string("10.0.0.5"),
+                "port" => Value::test_int(22),
+                "owner" => Value::test_string("ops"),
+            },
+        );
+        assert_eq!(
+            result,
+            record! {
+                "address" => Value::test_string("10.0.0.5"),
+                "port" => Value::test_int(22),
+                "is_open" => Value::test_bool(true),
+                "owner" => Value::test_string("ops"),
+            }
+        );
+    }
+
+    #[test]
+    fn colliding_input_columns_get_a_prefix() {
+        let mut result = record! {
+            "port" => Value::test_int(22),
+            "is_open" => Value::test_bool(true),
+            "target_is_open" => Value::test_nothing(),
+        };
+        keep_columns(
+            &mut result,
+            record! {
+                "port" => Value::test_int(22),
+                "is_open" => Value::test_string("expected"),
+            },
+        );
+        assert_eq!(
+            result,
+            record! {
+                "port" => Value::test_int(22),
+                "is_open" => Value::test_bool(true),
+                "target_is_open" => Value::test_nothing(),
+                "target_target_is_open" => Value::test_string("expected"),
+            }
+        );
     }
 }
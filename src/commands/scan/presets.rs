@@ -0,0 +1,120 @@
+use nu_protocol::{LabeledError, Span, Value};
+
+/// Most frequently open tcp ports, most common first (nmap-services frequency order)
+const TOP_TCP_PORTS: &[u16] = &[
+    80, 23, 443, 21, 22, 25, 3389, 110, 445, 139, 143, 53, 135, 3306, 8080, 1723, 111, 995, 993,
+    5900, 1025, 587, 8888, 199, 1720, 465, 548, 113, 81, 6001, 10000, 514, 5060, 179, 1026, 2000,
+    8443, 8000, 32768, 554, 26, 1433, 49152, 2001, 515, 8008, 49154, 1027, 5666, 646, 5000, 5631,
+    631, 49153, 8081, 2049, 88, 79, 5800, 106, 2121, 1110, 49155, 6000, 513, 990, 5357, 427, 49156,
+    543, 544, 5101, 144, 7, 389, 8009, 3128, 444, 9999, 5009, 7070, 5190, 3000, 5432, 1900, 3986,
+    13, 1029, 9, 5051, 6646, 49157, 1028, 873, 1755, 2717, 4899, 9100, 119, 37,
+];
+
+/// Most frequently open udp ports, most common first (nmap-services frequency order)
+const TOP_UDP_PORTS: &[u16] = &[
+    631, 161, 137, 123, 138, 1434, 445, 135, 67, 53, 139, 500, 68, 520, 1900, 4500, 514, 49152,
+    162, 69, 5353, 111, 49154, 1701, 998, 996, 997, 999, 3283, 49153, 1812, 136, 2222, 2049, 32768,
+    5060, 1025, 1433, 3456, 80, 20031, 1026, 7, 1646, 1645, 593, 518, 2048, 626, 1027,
+];
+
+/// Port groups selectable with `--profile`
+const PROFILES: &[(&str, &[u16])] = &[
+    (
+        "web",
+        &[
+            80, 443, 8000, 8008, 8080, 8081, 8443, 8888, 3000, 5000, 9000,
+        ],
+    ),
+    (
+        "db",
+        &[
+            1433, 1521, 3306, 5432, 5984, 6379, 7474, 8086, 9042, 9200, 11211, 26257, 27017,
+        ],
+    ),
+    ("mail", &[25, 110, 143, 465, 587, 993, 995, 2525]),
+    (
+        "remote-admin",
+        &[22, 23, 2222, 3389, 5800, 5900, 5985, 5986, 10000],
+    ),
+    (
+        "k8s",
+        &[443, 2379, 2380, 6443, 10250, 10255, 10256, 10257, 10259],
+    ),
+];
+
+/// Most common ports of the protocol, most common first
+pub(super) fn top_port_list(udp: bool) -> &'static [u16] {
+    match udp {
+        true => TOP_UDP_PORTS,
+        false => TOP_TCP_PORTS,
+    }
+}
+
+/// The `n` most common ports of the protocol, `n` is at most the length of [`top_port_list`]
+pub(super) fn top_ports(count: usize, udp: bool) -> Vec<u16> {
+    top_port_list(udp).iter().take(count).copied().collect()
+}
+
+/// Ports of a profile. Profiles of the plugin configuration
+/// (`$env.config.plugins.port_extension.profiles`) take precedence over the built-in ones.
+pub(super) fn profile_ports(
+    name: &str,
+    span: Span,
+    plugin_config: Option<&Value>,
+) -> Result<Vec<u16>, LabeledError> {
+    let configured = plugin_config
+        .and_then(|config| config.as_record().ok())
+        .and_then(|config| config.get("profiles"))
+        .and_then(|profiles| profiles.as_record().ok());
+    if let Some(ports) = configured.and_then(|profiles| profiles.get(name)) {
+        return ports
+            .as_list()?
+            .iter()
+            .map(|port| {
+                u16::try_from(port.as_int()?).map_err(|_| {
+                    LabeledError::new(format!("profile `{}` contains an invalid port", name))
+                        .with_label("not a valid port number", port.span())
+                })
+            })
+            .collect();
+    }
+    match PROFILES.iter().find(|(profile, _)| *profile == name) {
+        Some((_, ports)) => Ok(ports.to_vec()),
+        None => {
+            let mut names = PROFILES
+                .iter()
+                .map(|(profile, _)| profile.to_string())
+                .collect::<Vec<_>>();
+            names.extend(
+                configured
+                    .into_iter()
+                    .flat_map(|profiles| profiles.columns().cloned()),
+            );
+            Err(LabeledError::new(format!("unknown profile `{}`", name))
+                .with_label(format!("expected one of: {}", names.join(", ")), span))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_port_lists_have_no_duplicates() {
+        for udp in [false, true] {
+            let mut ports = top_port_list(udp).to_vec();
+            ports.sort_unstable();
+            ports.dedup();
+            assert_eq!(ports.len(), top_port_list(udp).len());
+        }
+    }
+
+    #[test]
+    fn top_ports_are_most_common_first() {
+        assert_eq!(top_ports(3, false), vec![80, 23, 443]);
+        assert_eq!(top_ports(2, true), vec![631, 161]);
+        assert_eq!(top_ports(100, false).len(), 100);
+        assert_eq!(top_ports(50, true).len(), 50);
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    fmt::{Display, Formatter},
+    io::ErrorKind,
+};
+
+use super::socket::SocketSetting;
+
+/// Stage of a probe that failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ProbeErrorKind {
+    /// Could not create or bind the local socket
+    Bind,
+    /// Could not apply an option (timeouts, ...) to the local socket
+    SocketOption,
+    /// The target actively refused the connection (TCP RST or ICMP port-unreachable)
+    Refused,
+    /// The target did not answer in time
+    Timeout,
+    /// Any other connection failure (unreachable network, reset, ...)
+    Connect,
+    Send,
+    Receive,
+    /// The target answered with something the requested protocol probe cannot parse
+    Response,
+    /// A `--script` step did not get the expected answer
+    Script,
+}
+
+impl ProbeErrorKind {
+    /// Local failures are caused by this host rather than by the target,
+    /// they are the ones `--strict` turns into errors
+    pub fn is_local(&self) -> bool {
+        matches!(self, ProbeErrorKind::Bind | ProbeErrorKind::SocketOption)
+    }
+}
+
+impl Display for ProbeErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ProbeErrorKind::Bind => "bind",
+            ProbeErrorKind::SocketOption => "socket_option",
+            ProbeErrorKind::Refused => "refused",
+            ProbeErrorKind::Timeout => "timeout",
+            ProbeErrorKind::Connect => "connect",
+            ProbeErrorKind::Send => "send",
+            ProbeErrorKind::Receive => "receive",
+            ProbeErrorKind::Response => "response",
+            ProbeErrorKind::Script => "script",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct ProbeError {
+    kind: ProbeErrorKind,
+    source: std::io::Error,
+    /// Argument whose setting could not be applied to the socket
+    setting: Option<SocketSetting>,
+}
+
+impl ProbeError {
+    pub fn new(kind: ProbeErrorKind, source: std::io::Error) -> Self {
+        Self {
+            kind,
+            source,
+            setting: None,
+        }
+    }
+
+    pub fn bind(setting: Option<SocketSetting>, source: std::io::Error) -> Self {
+        Self {
+            setting,
+            ..Self::new(ProbeErrorKind::Bind, source)
+        }
+    }
+
+    pub fn socket_option(setting: SocketSetting, source: std::io::Error) -> Self {
+        Self {
+            setting: Some(setting),
+            ..Self::new(ProbeErrorKind::SocketOption, source)
+        }
+    }
+
+    /// Classifies a failed connect/send/receive by its io error,
+    /// refusals and timeouts are reported as such regardless of the stage
+    pub fn from_io(stage: ProbeErrorKind, source: std::io::Error) -> Self {
+        let kind = match source.kind() {
+            ErrorKind::ConnectionRefused => ProbeErrorKind::Refused,
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => ProbeErrorKind::Timeout,
+            _ => stage,
+        };
+        Self::new(kind, source)
+    }
+
+    pub fn invalid_response(message: String) -> Self {
+        Self::new(
+            ProbeErrorKind::Response,
+            std::io::Error::new(ErrorKind::InvalidData, message),
+        )
+    }
+
+    pub fn script_failed(message: String) -> Self {
+        Self::new(ProbeErrorKind::Script, std::io::Error::other(message))
+    }
+
+    pub fn kind(&self) -> ProbeErrorKind {
+        self.kind
+    }
+
+    pub fn setting(&self) -> Option<SocketSetting> {
+        self.setting
+    }
+}
+
+impl Display for ProbeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ProbeErrorKind::Bind => write!(f, "cannot bind probe socket: {}", self.source),
+            ProbeErrorKind::SocketOption => {
+                write!(f, "cannot configure probe socket: {}", self.source)
+            }
+            ProbeErrorKind::Refused => write!(f, "connection refused: {}", self.source),
+            ProbeErrorKind::Timeout => write!(f, "timed out: {}", self.source),
+            ProbeErrorKind::Connect => write!(f, "cannot connect: {}", self.source),
+            ProbeErrorKind::Send => write!(f, "cannot send data: {}", self.source),
+            ProbeErrorKind::Receive => write!(f, "cannot receive data: {}", self.source),
+            ProbeErrorKind::Response => write!(f, "unexpected response: {}", self.source),
+            ProbeErrorKind::Script => write!(f, "script failed at {}", self.source),
+        }
+    }
+}
+
+impl std::error::Error for ProbeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    fmt::{Display, Formatter},
+    net::{IpAddr, ToSocketAddrs},
+    time::Duration,
+};
+
+use nu_plugin::{EngineInterface, EvaluatedCall};
+use nu_protocol::{LabeledError, Span, Value};
+
+use crate::helpers::AsValue;
+
+use super::{
+    interrupt::Interrupt, probe_error::ProbeErrorKind, scan::scan, scan_config::ScanConfig,
+    scan_result::PortState, target::Target,
+};
+
+/// How the target answered a probe
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Answer {
+    /// Connection established (tcp) or reply received (udp)
+    Open,
+    /// The host answered with a TCP RST or an ICMP port-unreachable
+    Refused,
+    Timeout,
+    /// Any other failure, the host did not answer
+    Error,
+}
+
+impl Answer {
+    pub fn is_open(&self) -> bool {
+        *self == Answer::Open
+    }
+
+    /// Whether the host answered at all, a refusal proves it is reachable
+    pub fn is_reply(&self) -> bool {
+        matches!(self, Answer::Open | Answer::Refused)
+    }
+}
+
+impl Display for Answer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Answer::Open => "open",
+            Answer::Refused => "refused",
+            Answer::Timeout => "timeout",
+            Answer::Error => "error",
+        })
+    }
+}
+
+impl AsValue for Answer {
+    fn as_value(self, span: Span) -> Value {
+        self.to_string().as_value(span)
+    }
+}
+
+/// Outcome of a single probe
+#[derive(Clone, Debug)]
+pub(crate) struct Reply {
+    pub answer: Answer,
+    /// Tcp handshake, udp round trip or time until the refusal, `None` without an answer
+    pub rtt: Option<Duration>,
+    pub error: Option<String>,
+}
+
+/// Repeated probes of one target, configured by the same flags as `port scan`.
+/// Used by the commands watching whether a port answers.
+pub(crate) struct Reachability {
+    cfg: ScanConfig,
+    interrupt: Interrupt,
+}
+
+impl Reachability {
+    /// Reads the target and the probe flags, a host name is resolved once up front
+    pub fn new(call: &EvaluatedCall, engine: &EngineInterface) -> Result<Self, LabeledError> {
+        let cfg = ScanConfig::try_from(call)?.with_signals(engine.signals().clone());
+        let cfg = match cfg.get_socket_addr() {
+            Ok(_) => cfg,
+            Err(_) => {
+                let address = resolve(
+                    cfg.target_address(),
+                    *cfg.target_port(),
+                    *cfg.address_span(),
+                )?;
+                cfg.with_target(&Target {
+                    address: Some(address.to_string()),
+                    span: *cfg.address_span(),
+                    ..Default::default()
+                })?
+            }
+        };
+        Ok(Self {
+            interrupt: cfg.interrupt().clone(),
+            cfg,
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        self.cfg.target_address()
+    }
+
+    pub fn port(&self) -> u16 {
+        *self.cfg.target_port()
+    }
+
+    /// Changes how long a single probe may take
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.cfg = self.cfg.clone().with_timeout(timeout);
+    }
+
+    /// Probes the target once, fails only when interrupted or on `--strict` local errors
+    pub fn probe(&self) -> Result<Reply, LabeledError> {
+        let result = scan(self.cfg.clone())?;
+        let error_kind = result.error_kind().map(str::to_string);
+        let answer = match result.state() {
+            PortState::Open => Answer::Open,
+            PortState::OpenFiltered => Answer::Timeout,
+            PortState::Closed => match error_kind.as_deref() {
+                Some(kind) if kind == ProbeErrorKind::Refused.to_string() => Answer::Refused,
+                Some(kind) if kind == ProbeErrorKind::Timeout.to_string() => Answer::Timeout,
+                _ => Answer::Error,
+            },
+        };
+        let rtt = match answer {
+            Answer::Open => result
+                .connect_time()
+                .or(result.first_byte_time())
+                .or(Some(result.elapsed())),
+            Answer::Refused => Some(result.elapsed()),
+            Answer::Timeout | Answer::Error => None,
+        };
+        Ok(Reply {
+            answer,
+            rtt,
+            error: result.error().map(str::to_string),
+        })
+    }
+
+    pub fn interrupted(&self) -> bool {
+        self.interrupt.triggered()
+    }
+
+    /// Waits between probes, returns `false` when interrupted
+    pub fn sleep(&self, duration: Duration) -> bool {
+        self.interrupt.sleep(duration).is_ok()
+    }
+}
+
+fn resolve(host: &str, port: u16, span: Span) -> Result<IpAddr, LabeledError> {
+    (host, port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .map(|address| address.ip())
+        .ok_or_else(|| {
+            LabeledError::new(format!("cannot resolve `{}`", host))
+                .with_label("not an IP address or a known host name", span)
+        })
+}
@@ -0,0 +1,371 @@
+use std::{
+    fmt::{Display, Formatter},
+    io::ErrorKind,
+    time::{Duration, Instant},
+};
+
+use nu_protocol::{Span, Value};
+use regex::bytes::Regex;
+
+use crate::helpers::AsValue;
+
+use super::{
+    connection::Connection,
+    probe_error::{ProbeError, ProbeErrorKind},
+    socket::SocketSetting,
+};
+
+/// Upper bound of a read when `--receive-byte-count` does not set one
+pub(super) const DEFAULT_READ_LIMIT: usize = 64 * 1024;
+
+/// Conditions ending the read of a tcp response, the first one met wins
+#[derive(Default, Clone, Debug)]
+pub(super) struct ReadUntil {
+    pub delimiter: Option<Vec<u8>>,
+    pub regex: Option<Regex>,
+    /// Stop once the peer stayed quiet for this long
+    pub idle: Option<Duration>,
+    pub to_eof: bool,
+}
+
+impl ReadUntil {
+    /// No condition was requested, only the byte count ends the read
+    pub fn is_empty(&self) -> bool {
+        self.delimiter.is_none() && self.regex.is_none() && self.idle.is_none() && !self.to_eof
+    }
+}
+
+/// Condition that ended a read, reported in the `read_end` column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ReadEnd {
+    Delimiter,
+    Regex,
+    Idle,
+    Eof,
+    /// The byte count (or the default limit) was reached
+    ByteCount,
+    Timeout,
+}
+
+impl Display for ReadEnd {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ReadEnd::Delimiter => "delimiter",
+            ReadEnd::Regex => "regex",
+            ReadEnd::Idle => "idle",
+            ReadEnd::Eof => "eof",
+            ReadEnd::ByteCount => "byte_count",
+            ReadEnd::Timeout => "timeout",
+        })
+    }
+}
+
+impl AsValue for ReadEnd {
+    fn as_value(self, span: Span) -> Value {
+        self.to_string().as_value(span)
+    }
+}
+
+/// Reads from `connection` until one of the `until` conditions is met, `limit` bytes were
+/// received or `timeout` elapsed. Without any condition running out of time is an error,
+/// matching the plain `--receive-byte-count` behavior. Also returns when the first byte arrived.
+pub(super) fn receive(
+    connection: &mut dyn Connection,
+    until: &ReadUntil,
+    limit: usize,
+    timeout: Duration,
+) -> Result<(Vec<u8>, ReadEnd, Option<Instant>), ProbeError> {
+    receive_buffered(connection, &mut Vec::new(), until, limit, timeout)
+}
+
+/// Like [`receive`], but first consumes the bytes `pending` kept from earlier reads on the
+/// same connection. Bytes received after a delimiter or regex match are put back into `pending`.
+pub(super) fn receive_buffered(
+    connection: &mut dyn Connection,
+    pending: &mut Vec<u8>,
+    until: &ReadUntil,
+    limit: usize,
+    timeout: Duration,
+) -> Result<(Vec<u8>, ReadEnd, Option<Instant>), ProbeError> {
+    let mut first_byte = None;
+    let (data, end) = read(connection, pending, until, limit, timeout, &mut first_byte)?;
+    Ok((data, end, first_byte))
+}
+
+fn read(
+    connection: &mut dyn Connection,
+    pending: &mut Vec<u8>,
+    until: &ReadUntil,
+    limit: usize,
+    timeout: Duration,
+    first_byte: &mut Option<Instant>,
+) -> Result<(Vec<u8>, ReadEnd), ProbeError> {
+    let deadline = Instant::now() + timeout;
+    let mut buffer = std::mem::take(pending);
+    if buffer.len() > limit {
+        *pending = buffer.split_off(limit);
+    }
+    if !buffer.is_empty() {
+        first_byte.get_or_insert_with(Instant::now);
+        if let Some(end) = matched(until, &mut buffer, 0, pending) {
+            return Ok((buffer, end));
+        }
+    }
+    let mut chunk = [0; 4096];
+    while buffer.len() < limit {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return timed_out(until, buffer, ErrorKind::TimedOut.into());
+        }
+        let (wait, end) = match until.idle {
+            Some(idle) if idle < remaining => (idle, ReadEnd::Idle),
+            _ => (remaining, ReadEnd::Timeout),
+        };
+        connection
+            .set_read_timeout(Some(wait))
+            .map_err(|e| ProbeError::socket_option(SocketSetting::Timeout, e))?;
+        let size = chunk.len().min(limit - buffer.len());
+        match connection.read(&mut chunk[..size]) {
+            Ok(0) => return Ok((buffer, ReadEnd::Eof)),
+            // tls peers often close without a close_notify alert
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                return Ok((buffer, ReadEnd::Eof));
+            }
+            Ok(size) => {
+                first_byte.get_or_insert_with(Instant::now);
+                // a delimiter may straddle two reads
+                let search_from = match &until.delimiter {
+                    Some(delimiter) => buffer.len().saturating_sub(delimiter.len() - 1),
+                    None => 0,
+                };
+                buffer.extend_from_slice(&chunk[..size]);
+                if let Some(end) = matched(until, &mut buffer, search_from, pending) {
+                    return Ok((buffer, end));
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return match end {
+                    ReadEnd::Idle => Ok((buffer, end)),
+                    _ => timed_out(until, buffer, err),
+                };
+            }
+            Err(err) => return Err(ProbeError::from_io(ProbeErrorKind::Receive, err)),
+        }
+    }
+    Ok((buffer, ReadEnd::ByteCount))
+}
+
+/// Looks for the delimiter (from `search_from` on) or the regex in `buffer`. On a match the
+/// bytes after it are moved to the front of `pending`.
+fn matched(
+    until: &ReadUntil,
+    buffer: &mut Vec<u8>,
+    search_from: usize,
+    pending: &mut Vec<u8>,
+) -> Option<ReadEnd> {
+    let (end, kind) = until
+        .delimiter
+        .as_ref()
+        .and_then(|delimiter| {
+            buffer[search_from..]
+                .windows(delimiter.len())
+                .position(|window| window == delimiter.as_slice())
+                .map(|position| (search_from + position + delimiter.len(), ReadEnd::Delimiter))
+        })
+        .or_else(|| {
+            let found = until.regex.as_ref()?.find(buffer)?;
+            Some((found.end(), ReadEnd::Regex))
+        })?;
+    let mut rest = buffer.split_off(end);
+    rest.append(pending);
+    *pending = rest;
+    Some(kind)
+}
+
+fn timed_out(
+    until: &ReadUntil,
+    buffer: Vec<u8>,
+    err: std::io::Error,
+) -> Result<(Vec<u8>, ReadEnd), ProbeError> {
+    match until.is_empty() {
+        true => Err(ProbeError::from_io(ProbeErrorKind::Receive, err)),
+        false => Ok((buffer, ReadEnd::Timeout)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        collections::VecDeque,
+        io::{self, Read, Write},
+    };
+
+    use super::*;
+
+    /// Connection replaying scripted reads, remembering the read timeouts it was given
+    #[derive(Default)]
+    struct Scripted {
+        reads: VecDeque<Result<Vec<u8>, ErrorKind>>,
+        waits: RefCell<Vec<Duration>>,
+    }
+
+    impl Scripted {
+        fn new(reads: impl IntoIterator<Item = Result<&'static [u8], ErrorKind>>) -> Self {
+            Self {
+                reads: reads
+                    .into_iter()
+                    .map(|read| read.map(<[u8]>::to_vec))
+                    .collect(),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl Read for Scripted {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.reads.pop_front() {
+                Some(Ok(mut data)) => {
+                    let size = data.len().min(buf.len());
+                    buf[..size].copy_from_slice(&data[..size]);
+                    if size < data.len() {
+                        self.reads.push_front(Ok(data.split_off(size)));
+                    }
+                    Ok(size)
+                }
+                Some(Err(kind)) => Err(kind.into()),
+                None => Ok(0),
+            }
+        }
+    }
+
+    impl Write for Scripted {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Connection for Scripted {
+        fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+            self.waits.borrow_mut().extend(timeout);
+            Ok(())
+        }
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn delimiter_split_across_reads() {
+        let mut connection = Scripted::new([Ok(&b"220 ready\r"[..]), Ok(b"\nleftover")]);
+        let until = ReadUntil {
+            delimiter: Some(b"\r\n".to_vec()),
+            ..Default::default()
+        };
+        let (data, end, first_byte) =
+            receive(&mut connection, &until, DEFAULT_READ_LIMIT, TIMEOUT).unwrap();
+        assert_eq!(data, b"220 ready\r\n");
+        assert_eq!(end, ReadEnd::Delimiter);
+        assert!(first_byte.is_some());
+    }
+
+    #[test]
+    fn regex_matches_before_the_limit() {
+        let mut connection = Scripted::new([Ok(&b"SSH-2.0-"[..]), Ok(b"OpenSSH_9.6\r\nmore")]);
+        let until = ReadUntil {
+            regex: Some(Regex::new(r"SSH-[\d.]+-\S+").unwrap()),
+            ..Default::default()
+        };
+        let (data, end, _) = receive(&mut connection, &until, 64, TIMEOUT).unwrap();
+        assert_eq!(data, b"SSH-2.0-OpenSSH_9.6");
+        assert_eq!(end, ReadEnd::Regex);
+    }
+
+    #[test]
+    fn limit_reached_before_any_delimiter() {
+        let mut connection = Scripted::new([Ok(&b"0123456789\r\n"[..])]);
+        let until = ReadUntil {
+            delimiter: Some(b"\r\n".to_vec()),
+            ..Default::default()
+        };
+        let (data, end, _) = receive(&mut connection, &until, 8, TIMEOUT).unwrap();
+        assert_eq!(data, b"01234567");
+        assert_eq!(end, ReadEnd::ByteCount);
+    }
+
+    #[test]
+    fn eof_ends_the_read() {
+        let mut connection = Scripted::new([Ok(&b"bye"[..])]);
+        let until = ReadUntil {
+            to_eof: true,
+            ..Default::default()
+        };
+        let (data, end, _) = receive(&mut connection, &until, DEFAULT_READ_LIMIT, TIMEOUT).unwrap();
+        assert_eq!(data, b"bye");
+        assert_eq!(end, ReadEnd::Eof);
+    }
+
+    #[test]
+    fn idle_ends_the_read_before_the_timeout() {
+        let idle = Duration::from_millis(50);
+        let mut connection = Scripted::new([Ok(&b"partial"[..]), Err(ErrorKind::WouldBlock)]);
+        let until = ReadUntil {
+            idle: Some(idle),
+            ..Default::default()
+        };
+        let (data, end, _) = receive(&mut connection, &until, DEFAULT_READ_LIMIT, TIMEOUT).unwrap();
+        assert_eq!(data, b"partial");
+        assert_eq!(end, ReadEnd::Idle);
+        assert!(connection.waits.borrow().iter().all(|wait| *wait == idle));
+    }
+
+    #[test]
+    fn timeout_ends_the_read_before_idle() {
+        let timeout = Duration::from_millis(50);
+        let mut connection = Scripted::new([Ok(&b"partial"[..]), Err(ErrorKind::TimedOut)]);
+        let until = ReadUntil {
+            idle: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let (data, end, _) = receive(&mut connection, &until, DEFAULT_READ_LIMIT, timeout).unwrap();
+        assert_eq!(data, b"partial");
+        assert_eq!(end, ReadEnd::Timeout);
+        assert!(
+            connection
+                .waits
+                .borrow()
+                .iter()
+                .all(|wait| *wait <= timeout)
+        );
+    }
+
+    #[test]
+    fn timeout_without_conditions_is_an_error() {
+        let mut connection = Scripted::new([Ok(&b"partial"[..]), Err(ErrorKind::WouldBlock)]);
+        let err = receive(&mut connection, &ReadUntil::default(), 64, TIMEOUT).unwrap_err();
+        assert_eq!(err.kind(), ProbeErrorKind::Timeout);
+    }
+
+    #[test]
+    fn bytes_after_a_match_are_kept_for_the_next_read() {
+        let mut connection =
+            Scripted::new([Ok(&b"+OK one\r\n+OK two\r\n+O"[..]), Ok(b"K three\r\n")]);
+        let until = ReadUntil {
+            delimiter: Some(b"\r\n".to_vec()),
+            ..Default::default()
+        };
+        let mut pending = vec![];
+        let mut next = || {
+            receive_buffered(&mut connection, &mut pending, &until, 64, TIMEOUT)
+                .unwrap()
+                .0
+        };
+        assert_eq!(next(), b"+OK one\r\n");
+        assert_eq!(next(), b"+OK two\r\n");
+        assert_eq!(next(), b"+OK three\r\n");
+    }
+}
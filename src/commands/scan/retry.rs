@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+/// Pause before the first retry when `--backoff` is not given
+pub(super) const DEFAULT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// How often and how patiently a failed probe is repeated
+#[derive(Default, Clone, Copy, Debug)]
+pub(super) struct RetryPolicy {
+    pub retries: u32,
+    pub backoff: Duration,
+    /// Double the pause after every retry
+    pub exponential: bool,
+    /// Pick each pause at random between half and all of its nominal length
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// Pause before the given retry (counting from 1)
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = match self.exponential {
+            true => self
+                .backoff
+                .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1))),
+            false => self.backoff,
+        };
+        match self.jitter && !delay.is_zero() {
+            true => rand::random_range(delay / 2..=delay),
+            false => delay,
+        }
+    }
+}
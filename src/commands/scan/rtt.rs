@@ -0,0 +1,144 @@
+use std::{collections::HashMap, net::IpAddr, sync::Mutex, time::Duration};
+
+/// Round-trip times measured per host by `--adaptive`, shared by every target of one call
+#[derive(Debug)]
+pub(super) struct RttTracker {
+    hosts: Mutex<HashMap<IpAddr, RttEstimate>>,
+    min_timeout: Duration,
+    max_timeout: Duration,
+}
+
+/// Smoothed round-trip time and its variation as described in RFC 6298
+#[derive(Clone, Copy, Debug)]
+struct RttEstimate {
+    smoothed: Duration,
+    variation: Duration,
+}
+
+impl RttTracker {
+    pub fn new(min_timeout: Duration, max_timeout: Duration) -> Self {
+        Self {
+            hosts: Mutex::new(HashMap::new()),
+            min_timeout,
+            max_timeout,
+        }
+    }
+
+    /// Timeout derived from the measurements of `host`, `None` until it answered once.
+    /// Each of the `retries` of a probe doubles it, as RFC 6298 backs off retransmissions.
+    pub fn timeout(&self, host: IpAddr, retries: u32) -> Option<Duration> {
+        let hosts = self.hosts.lock().unwrap();
+        let estimate = hosts.get(&host)?;
+        let timeout = (estimate.smoothed + estimate.variation * 4)
+            .saturating_mul(2u32.saturating_pow(retries));
+        Some(timeout.clamp(self.min_timeout, self.max_timeout))
+    }
+
+    pub fn record(&self, host: IpAddr, sample: Duration) {
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts
+            .entry(host)
+            .and_modify(|estimate| {
+                let deviation = estimate.smoothed.abs_diff(sample);
+                estimate.variation = (estimate.variation * 3 + deviation) / 4;
+                estimate.smoothed = (estimate.smoothed * 7 + sample) / 8;
+            })
+            .or_insert(RttEstimate {
+                smoothed: sample,
+                variation: sample / 2,
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const HOST: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn tracker() -> RttTracker {
+        RttTracker::new(ms(10), ms(10_000))
+    }
+
+    fn estimate(tracker: &RttTracker) -> (Duration, Duration) {
+        let estimate = tracker.hosts.lock().unwrap()[&HOST];
+        (estimate.smoothed, estimate.variation)
+    }
+
+    #[test]
+    fn no_timeout_before_the_first_sample() {
+        let tracker = tracker();
+        assert_eq!(tracker.timeout(HOST, 0), None);
+        tracker.record(IpAddr::V4(Ipv4Addr::LOCALHOST), ms(100));
+        assert_eq!(tracker.timeout(HOST, 0), None);
+    }
+
+    #[test]
+    fn first_sample_sets_srtt_and_half_as_rttvar() {
+        let tracker = tracker();
+        tracker.record(HOST, ms(100));
+        assert_eq!(estimate(&tracker), (ms(100), ms(50)));
+        assert_eq!(tracker.timeout(HOST, 0), Some(ms(300)));
+    }
+
+    #[test]
+    fn later_samples_update_rttvar_before_srtt() {
+        let tracker = tracker();
+        tracker.record(HOST, ms(100));
+        // rttvar = 3/4 × 50 + 1/4 × |100 - 180|, srtt = 7/8 × 100 + 1/8 × 180
+        tracker.record(HOST, ms(180));
+        assert_eq!(
+            estimate(&tracker),
+            (ms(110), ms(57) + Duration::from_micros(500))
+        );
+        // rttvar = 3/4 × 57.5 + 1/4 × |110 - 110|, srtt = 110
+        tracker.record(HOST, ms(110));
+        assert_eq!(
+            estimate(&tracker),
+            (ms(110), ms(43) + Duration::from_micros(125))
+        );
+        assert_eq!(
+            tracker.timeout(HOST, 0),
+            Some(ms(282) + Duration::from_micros(500))
+        );
+    }
+
+    #[test]
+    fn steady_samples_shrink_the_variation() {
+        let tracker = tracker();
+        for _ in 0..50 {
+            tracker.record(HOST, ms(20));
+        }
+        let (smoothed, variation) = estimate(&tracker);
+        assert_eq!(smoothed, ms(20));
+        assert!(variation < Duration::from_micros(10), "{variation:?}");
+    }
+
+    #[test]
+    fn timeout_is_clamped() {
+        let tracker = RttTracker::new(ms(100), ms(1000));
+        tracker.record(HOST, ms(1));
+        assert_eq!(tracker.timeout(HOST, 0), Some(ms(100)));
+
+        let tracker = RttTracker::new(ms(100), ms(1000));
+        tracker.record(HOST, ms(800));
+        assert_eq!(tracker.timeout(HOST, 0), Some(ms(1000)));
+    }
+
+    #[test]
+    fn retries_back_off_up_to_the_maximum() {
+        let tracker = tracker();
+        tracker.record(HOST, ms(100));
+        assert_eq!(tracker.timeout(HOST, 1), Some(ms(600)));
+        assert_eq!(tracker.timeout(HOST, 2), Some(ms(1200)));
+        assert_eq!(tracker.timeout(HOST, 5), Some(ms(9600)));
+        assert_eq!(tracker.timeout(HOST, 6), Some(ms(10_000)));
+        assert_eq!(tracker.timeout(HOST, u32::MAX), Some(ms(10_000)));
+    }
+}
@@ -1,113 +1,369 @@
 use std::{
-    io::{Read, Write},
-    net::{SocketAddr, TcpStream},
+    io::Write,
+    net::SocketAddr,
     time::{Duration, Instant},
 };
 
-use nu_protocol::LabeledError;
-use std::net::UdpSocket;
+use nu_protocol::{LabeledError, ShellError, Span};
+
+use crate::helpers::AsValue;
 
 use super::{
+    connection::{self, TlsTarget},
+    detect::{detect, read_banner},
+    http::{self, HTTP_ALPN, HttpReport},
+    probe_error::{ProbeError, ProbeErrorKind},
+    receive::{DEFAULT_READ_LIMIT, ReadEnd, receive},
     scan_config::ScanConfig,
-    scan_result::{ScanResult, ScanResultBuilder},
+    scan_result::{PortState, ScanResult, ScanResultBuilder},
+    script::{self, ScriptReport},
+    socket::SocketSetting,
+    tls::TlsReport,
+    udp_probe::default_payload,
 };
 
-pub(super) fn scan(cfg: ScanConfig) -> Result<ScanResult<String>, LabeledError> {
-    let mut result = ScanResultBuilder::default();
-    result.address(cfg.target_address().to_owned());
-    result.port(cfg.target_port().to_owned());
-    let now = Instant::now();
-    let check_method = match cfg.udp() {
-        true => check_udp,
-        false => check_tcp,
-    };
-    let (is_open, data) = check_method(
-        cfg.get_socket_addr()?,
-        cfg.timeout().to_owned(),
-        cfg.send().to_owned(),
-        cfg.receive_byte_count().to_owned(),
-    );
-    result.elapsed(now.elapsed());
-    result.is_open(is_open);
-    result.received_data(data);
+/// Largest payload a single udp datagram can carry
+const MAX_DATAGRAM_SIZE: usize = 65_507;
 
-    Ok(result.build().unwrap())
+/// ALPN protocols offered by `--tls`, STARTTLS upgrades offer none
+const TLS_ALPN: &[&str] = &["h2", "http/1.1"];
+
+#[derive(Default)]
+struct ProbeOutcome {
+    state: PortState,
+    data: Option<Vec<u8>>,
+    read_end: Option<ReadEnd>,
+    /// Time the tcp handshake took
+    connect_time: Option<Duration>,
+    send_time: Option<Duration>,
+    first_byte_time: Option<Duration>,
+    tls: Option<TlsReport>,
+    http: Option<HttpReport>,
+    script: Option<ScriptReport>,
+    /// What the service sent unasked on the scan's connection, read for `--detect`
+    banner: Option<Vec<u8>>,
 }
-fn check_udp(
-    address: SocketAddr,
-    timeout: Duration,
-    send_data: Option<Vec<u8>>,
-    receive_byte_count: Option<i64>,
-) -> (bool, Option<Vec<u8>>) {
-    match UdpSocket::bind("0.0.0.0:0") {
-        Ok(socket) => {
-            if let Err(err) = socket.set_read_timeout(Some(timeout)) {
-                eprintln!("Error setting read timeout, {}", err);
-                return (false, None);
-            }
 
-            if let Err(err) = socket.set_write_timeout(Some(timeout)) {
-                eprintln!("Error setting write timeout, {}", err);
-                return (false, None);
-            }
-            if let Err(err) = socket.send_to(&send_data.unwrap(), address) {
-                eprintln!("Error sending data, {}", err);
-                return (false, None);
-            }
+impl ProbeOutcome {
+    fn new(state: PortState, data: Option<Vec<u8>>) -> Self {
+        Self {
+            state,
+            data,
+            ..Default::default()
+        }
+    }
+}
 
-            let mut buffer = vec![0; receive_byte_count.unwrap() as usize];
-            match socket.peek(&mut buffer) {
-                Ok(size) => (true, Some(buffer[..size].to_vec())),
-                Err(err) => {
-                    eprintln!("Error receiving data, {}", err);
-                    (false, None)
-                }
+type ProbeResult = Result<ProbeOutcome, ProbeError>;
+
+pub(super) fn scan(mut cfg: ScanConfig) -> Result<ScanResult<String>, LabeledError> {
+    let mut result = ScanResultBuilder::default();
+    result.address(cfg.target_address().to_owned());
+    result.port(cfg.target_port().to_owned());
+    let address = cfg.get_socket_addr()?;
+    let mut attempts = vec![];
+    let started = Instant::now();
+    let probe = loop {
+        cfg.adapt_timeout(address.ip(), attempts.len() as u32);
+        cfg.pacing()
+            .wait(cfg.interrupt())
+            .map_err(|_| interrupted(&cfg))?;
+        let now = Instant::now();
+        let probe = match cfg.udp() {
+            true => {
+                let send = match cfg.probe() {
+                    Some(probe) => Some(probe.request()),
+                    None => cfg.send().to_owned(),
+                };
+                check_udp(address, &cfg, send)
             }
+            false => check_tcp(address, &cfg),
+        };
+        let elapsed = now.elapsed();
+        // whatever the probe saw after ctrl-c says nothing about the target
+        if cfg.interrupt().triggered() {
+            return Err(interrupted(&cfg));
         }
-        Err(err) => {
-            eprintln!("Error binding UDP socket, {}", err);
-            (false, None)
+        attempts.push(elapsed);
+        if let Some(rtt) = cfg.adaptive()
+            && let Some(sample) = rtt_sample(&probe, elapsed)
+        {
+            rtt.record(address.ip(), sample);
         }
+        let retry = attempts.len() as u32;
+        if retry > cfg.retry().retries || !should_retry(&probe) {
+            break probe;
+        }
+        cfg.interrupt()
+            .sleep(cfg.retry().delay(retry))
+            .map_err(|_| interrupted(&cfg))?;
+    };
+    result.elapsed(*attempts.last().unwrap());
+    if cfg.retry().retries > 0 {
+        result.attempts(Some(attempts));
     }
-}
-fn check_tcp(
-    address: SocketAddr,
-    timeout: Duration,
-    send_data: Option<Vec<u8>>,
-    receive_byte_count: Option<i64>,
-) -> (bool, Option<Vec<u8>>) {
-    match TcpStream::connect_timeout(&address, timeout) {
-        Ok(mut stream) => {
-            if let Err(err) = stream.set_read_timeout(Some(timeout)) {
-                eprintln!("Error setting read timeout, {}", err);
-                return (false, None);
-            }
-            if let Err(err) = stream.set_write_timeout(Some(timeout)) {
-                eprintln!("Error setting read timeout, {}", err);
-                return (false, None);
+    if cfg.adaptive().is_some() {
+        result.timeout(Some(*cfg.timeout()));
+    }
+    match probe {
+        Ok(ProbeOutcome {
+            state,
+            data,
+            read_end,
+            connect_time,
+            send_time,
+            first_byte_time,
+            tls,
+            http,
+            script,
+            banner,
+        }) => {
+            result.connect_time(connect_time);
+            result.send_time(send_time);
+            result.first_byte_time(first_byte_time);
+            result.read_end(read_end);
+            result.tls(tls);
+            result.http(http);
+            if let Some(db) = cfg.signatures()
+                && !cfg.udp()
+                && state == PortState::Open
+            {
+                result.detection(Some(detect(
+                    address,
+                    cfg.socket(),
+                    cfg.timeout().to_owned(),
+                    cfg.interrupt(),
+                    data.as_deref(),
+                    banner.as_deref(),
+                    db,
+                )));
             }
-            if let Some(data) = send_data {
-                if let Err(err) = stream.write_all(&data) {
-                    eprintln!("Error writing to socket stream, {}", err);
-                    return (false, None);
+            if let Some(probe) = cfg.probe() {
+                let parsed = data.as_deref().map(|data| probe.parse(data));
+                if let Some(Err(err)) = &parsed {
+                    let err = ProbeError::invalid_response(err.to_owned());
+                    result.error(Some(err.to_string()));
+                    result.error_kind(Some(err.kind().to_string()));
                 }
+                let parsed = parsed.and_then(Result::ok);
+                result.probe_result(Some(parsed.as_value(Span::unknown())));
             }
-            if let Some(receive_byte_count) = receive_byte_count {
-                // eprintln!("Wait to read the amount of bytes requested");
-                let buffer: Result<Vec<u8>, std::io::Error> =
-                    stream.bytes().take(receive_byte_count as usize).collect();
-                match buffer {
-                    Ok(data) => {
-                        return (true, Some(data));
-                    }
-                    Err(err) => {
-                        eprintln!("Error reading from socket stream, {}", err);
-                        return (false, None);
-                    }
-                };
+            if let Some(err) = script.as_ref().and_then(ScriptReport::error) {
+                let err = ProbeError::script_failed(err.to_owned());
+                result.error(Some(err.to_string()));
+                result.error_kind(Some(err.kind().to_string()));
             }
-            (true, None)
+            result.script(script);
+            result.state(state);
+            result.received_data(data);
         }
-        Err(_) => (false, None),
+        Err(err) if *cfg.strict() && err.kind().is_local() => {
+            return Err(strict_error(&cfg, err));
+        }
+        Err(err) => {
+            result.state(PortState::Closed);
+            result.received_data(None);
+            result.error(Some(err.to_string()));
+            result.error_kind(Some(err.kind().to_string()));
+        }
+    }
+    if cfg.interrupt().triggered() {
+        return Err(interrupted(&cfg));
+    }
+    result.total_time(started.elapsed());
+
+    Ok(result.build().unwrap())
+}
+
+fn interrupted(cfg: &ScanConfig) -> LabeledError {
+    ShellError::Interrupted {
+        span: *cfg.address_span(),
+    }
+    .into()
+}
+
+/// Round-trip time measured by an attempt: the tcp handshake, a udp reply or a refusal
+fn rtt_sample(probe: &ProbeResult, elapsed: Duration) -> Option<Duration> {
+    match probe {
+        Ok(outcome) if outcome.connect_time.is_some() => outcome.connect_time,
+        Ok(outcome) if outcome.state == PortState::Open => Some(elapsed),
+        Err(err) if err.kind() == ProbeErrorKind::Refused => Some(elapsed),
+        _ => None,
+    }
+}
+
+/// Silence and transient failures may be a lost packet, refusals and local errors are final
+fn should_retry(probe: &ProbeResult) -> bool {
+    match probe {
+        Ok(outcome) => outcome.state == PortState::OpenFiltered,
+        Err(err) => matches!(
+            err.kind(),
+            ProbeErrorKind::Timeout
+                | ProbeErrorKind::Connect
+                | ProbeErrorKind::Send
+                | ProbeErrorKind::Receive
+        ),
+    }
+}
+
+/// Turns a local probe failure into an error pointing at the argument that caused it
+fn strict_error(cfg: &ScanConfig, err: ProbeError) -> LabeledError {
+    let (label, span) = match (err.kind(), err.setting()) {
+        (ProbeErrorKind::Bind, Some(SocketSetting::Bind)) => (
+            "the probe socket cannot be bound to this address",
+            *cfg.bind_span(),
+        ),
+        (ProbeErrorKind::Bind, _) => (
+            "no local socket can be bound for this address",
+            *cfg.address_span(),
+        ),
+        (_, setting) => (
+            "cannot apply this setting to the probe socket",
+            cfg.setting_span(setting.unwrap_or(SocketSetting::Timeout)),
+        ),
+    };
+    LabeledError::new(err.to_string())
+        .with_code(format!("port_scan::{}", err.kind()))
+        .with_label(label, span)
+}
+
+fn check_udp(address: SocketAddr, cfg: &ScanConfig, send_data: Option<Vec<u8>>) -> ProbeResult {
+    let timeout = *cfg.timeout();
+    let socket = cfg.socket().bind_udp(address)?;
+    socket
+        .set_read_timeout(Some(timeout))
+        .map_err(|e| ProbeError::socket_option(SocketSetting::Timeout, e))?;
+    socket
+        .set_write_timeout(Some(timeout))
+        .map_err(|e| ProbeError::socket_option(SocketSetting::Timeout, e))?;
+    // a connected socket is required to receive ICMP port-unreachable as ECONNREFUSED
+    socket
+        .connect(address)
+        .map_err(|e| ProbeError::from_io(ProbeErrorKind::Connect, e))?;
+    let payload = send_data.unwrap_or_else(|| default_payload(address.port()));
+    let send_started = Instant::now();
+    socket
+        .send(&payload)
+        .map_err(|e| ProbeError::from_io(ProbeErrorKind::Send, e))?;
+    let sent = Instant::now();
+
+    // a datagram never exceeds MAX_DATAGRAM_SIZE, a larger count would only waste memory
+    let buffer_size = cfg.receive_byte_count().map_or(MAX_DATAGRAM_SIZE, |count| {
+        (count as usize).min(MAX_DATAGRAM_SIZE)
+    });
+    let mut buffer = vec![0; buffer_size];
+    let received = cfg.interrupt().read(
+        Some(timeout),
+        |slice| socket.set_read_timeout(Some(slice)),
+        || socket.recv(&mut buffer),
+    );
+    let mut outcome = match received {
+        Ok(size) => ProbeOutcome {
+            first_byte_time: Some(sent.elapsed()),
+            ..ProbeOutcome::new(PortState::Open, Some(buffer[..size].to_vec()))
+        },
+        Err(err) => match ProbeError::from_io(ProbeErrorKind::Receive, err) {
+            // silence is expected from open udp ports that do not understand the payload
+            err if err.kind() == ProbeErrorKind::Timeout => {
+                ProbeOutcome::new(PortState::OpenFiltered, None)
+            }
+            err => return Err(err),
+        },
+    };
+    outcome.send_time = Some(sent - send_started);
+    Ok(outcome)
+}
+
+fn check_tcp(address: SocketAddr, cfg: &ScanConfig) -> ProbeResult {
+    let tls = cfg.tls().then(|| TlsTarget {
+        sni: cfg.sni().as_deref(),
+        starttls: *cfg.starttls(),
+        alpn: match (cfg.starttls(), cfg.http()) {
+            (Some(_), _) => &[],
+            (None, Some(_)) => HTTP_ALPN,
+            (None, None) => TLS_ALPN,
+        },
+    });
+    let session = connection::open(address, cfg, tls)?;
+    let mut outcome = ProbeOutcome::new(PortState::Open, None);
+    outcome.connect_time = Some(session.connect_time);
+    outcome.tls = session.tls.clone();
+    if let Some(path) = cfg.http() {
+        outcome.http = Some(http::probe(address, cfg, path, session));
+        return Ok(outcome);
+    }
+    // without a connection the port is open but the handshake failed, the tls report describes why
+    let Some(mut connection) = session.connection else {
+        return Ok(outcome);
+    };
+    let limit = cfg
+        .receive_byte_count()
+        .map_or(DEFAULT_READ_LIMIT, |count| count as usize);
+    if let Some(steps) = cfg.script() {
+        let report = script::run(connection.as_mut(), steps, limit, *cfg.timeout());
+        outcome.data = Some(report.received());
+        outcome.script = Some(report);
+        return Ok(outcome);
+    }
+    if let Some(data) = cfg.send() {
+        let send_started = Instant::now();
+        connection
+            .write_all(data)
+            .map_err(|e| ProbeError::from_io(ProbeErrorKind::Send, e))?;
+        outcome.send_time = Some(send_started.elapsed());
+    }
+    if cfg.receive_byte_count().is_some() || !cfg.read_until().is_empty() {
+        let waiting = Instant::now();
+        let (data, end, first_byte) =
+            receive(connection.as_mut(), cfg.read_until(), limit, *cfg.timeout())?;
+        outcome.data = Some(data);
+        outcome.read_end = Some(end);
+        outcome.first_byte_time = first_byte.map(|first_byte| first_byte - waiting);
+    } else if cfg.signatures().is_some() && cfg.send().is_none() {
+        outcome.banner = Some(read_banner(connection.as_mut(), *cfg.timeout()));
+    }
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::UdpSocket, thread};
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(300);
+
+    fn scan_udp(address: SocketAddr) -> ScanResult<String> {
+        scan(ScanConfig::for_target(address, true, TIMEOUT)).unwrap()
+    }
+
+    #[test]
+    fn ipv6_udp_reply_is_open() {
+        let responder = UdpSocket::bind("[::1]:0").unwrap();
+        let address = responder.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buffer = [0; 512];
+            let (_, peer) = responder.recv_from(&mut buffer).unwrap();
+            responder.send_to(b"pong", peer).unwrap();
+        });
+        let result = scan_udp(address);
+        assert_eq!(result.state(), PortState::Open);
+        assert!(result.first_byte_time().is_some());
+    }
+
+    #[test]
+    fn ipv6_udp_unbound_port_is_closed() {
+        let address = UdpSocket::bind("[::1]:0").unwrap().local_addr().unwrap();
+        let result = scan_udp(address);
+        assert_eq!(result.state(), PortState::Closed);
+        assert_eq!(result.error_kind(), Some("refused"));
+    }
+
+    #[test]
+    fn ipv6_udp_silence_is_open_filtered() {
+        let responder = UdpSocket::bind("[::1]:0").unwrap();
+        let result = scan_udp(responder.local_addr().unwrap());
+        assert_eq!(result.state(), PortState::OpenFiltered);
+        assert_eq!(result.error(), None);
     }
 }
@@ -1,13 +1,37 @@
-use std::{net::SocketAddr, str::FromStr, time::Duration};
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
 use derive_builder::Builder;
 use derive_getters::Getters;
 use nu_plugin::EvaluatedCall;
-use nu_protocol::{LabeledError, Span, Value};
+use nu_protocol::{LabeledError, Signals, Span, Spanned, Value, engine::Closure};
+use regex::bytes::Regex;
 
 use crate::helpers::FlagHelper;
 
-const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+use super::{
+    detect::SignatureDb,
+    interrupt::Interrupt,
+    pacing::Pacing,
+    presets,
+    receive::ReadUntil,
+    retry::{DEFAULT_BACKOFF, RetryPolicy},
+    rtt::RttTracker,
+    scheduler::Schedule,
+    script::ScriptStep,
+    socket::{SocketOptions, SocketSetting},
+    target::Target,
+    timing::{Timing, TimingProfile},
+    tls::StartTls,
+    udp_probe::UdpProbe,
+};
+
+const DEFAULT_HTTP_BODY_BYTES: usize = 1024;
 
 #[derive(Default, Clone, Builder, Debug, Getters)]
 pub(super) struct ScanConfig {
@@ -16,18 +40,160 @@ pub(super) struct ScanConfig {
     timeout: Duration,
     send: Option<Vec<u8>>,
     receive_byte_count: Option<i64>,
+    read_until: ReadUntil,
+    script: Option<Vec<ScriptStep>>,
+    check: Option<Spanned<Closure>>,
+    retry: RetryPolicy,
+    socket: SocketOptions,
+    pacing: Pacing,
+    schedule: Schedule,
+    /// Round-trip times learned with `--adaptive`
+    adaptive: Option<Arc<RttTracker>>,
+    #[builder(default)]
+    interrupt: Interrupt,
+    top_ports: Option<usize>,
+    profile: Option<Spanned<String>>,
     udp: bool,
+    strict: bool,
+    /// Signatures used by `--detect`, loaded once for all targets
+    signatures: Option<Arc<SignatureDb>>,
+    probe: Option<UdpProbe>,
+    tls: bool,
+    sni: Option<String>,
+    starttls: Option<StartTls>,
+    http: Option<String>,
+    http_body_bytes: usize,
+    follow_redirects: bool,
+    address_span: Span,
+    timeout_span: Span,
+    bind_span: Span,
+    interface_span: Span,
+    ttl_span: Span,
+    tos_span: Span,
+    rst_close_span: Span,
 }
 
 impl ScanConfig {
     pub fn get_socket_addr(&self) -> Result<SocketAddr, LabeledError> {
-        let addr = format!("{}:{}", self.target_address, self.target_port);
-        SocketAddr::from_str(&addr).map_err(|e| {
-            LabeledError::new(format!(
-                "cannot parse given address as socket address: {}",
-                e
-            ))
-        })
+        // accept both `::1` and the bracketed `[::1]` form for ipv6 targets
+        let addr = self
+            .target_address
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        IpAddr::from_str(addr)
+            .map(|ip| SocketAddr::new(ip, self.target_port))
+            .map_err(|e| {
+                LabeledError::new(format!(
+                    "cannot parse given address as socket address: {}",
+                    e
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+impl ScanConfig {
+    /// Scan of `address` with every other setting left at its default
+    pub fn for_target(address: SocketAddr, udp: bool, timeout: Duration) -> Self {
+        Self {
+            target_address: address.ip().to_string(),
+            target_port: address.port(),
+            timeout,
+            udp,
+            ..Default::default()
+        }
+    }
+}
+
+impl ScanConfig {
+    /// Span of the argument that configured a socket setting
+    pub fn setting_span(&self, setting: SocketSetting) -> Span {
+        match setting {
+            SocketSetting::Timeout => self.timeout_span,
+            SocketSetting::Bind => self.bind_span,
+            SocketSetting::Interface => self.interface_span,
+            SocketSetting::Ttl => self.ttl_span,
+            SocketSetting::Tos => self.tos_span,
+            SocketSetting::RstClose => self.rst_close_span,
+        }
+    }
+
+    /// Replaces the timeout by the one learned for `host` with `--adaptive`, backed off for `retries`
+    pub fn adapt_timeout(&mut self, host: IpAddr, retries: u32) {
+        if let Some(timeout) = self
+            .adaptive
+            .as_ref()
+            .and_then(|rtt| rtt.timeout(host, retries))
+        {
+            self.timeout = timeout;
+        }
+    }
+
+    /// Lets ctrl-c cut the blocking steps of the scan short
+    pub fn with_signals(mut self, signals: Signals) -> Self {
+        self.interrupt = Interrupt::new(signals);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Whether both the address and the port were given as arguments
+    pub fn has_target(&self) -> bool {
+        !self.target_address.is_empty() && self.target_port != 0
+    }
+
+    /// Applies the overrides of a pipeline row on top of the flags
+    pub fn with_target(&self, target: &Target) -> Result<ScanConfig, LabeledError> {
+        let mut cfg = self.clone();
+        if let Some(address) = &target.address {
+            cfg.target_address = address.to_owned();
+            cfg.address_span = target.span;
+        }
+        if let Some(port) = target.port {
+            cfg.target_port = port;
+        }
+        if !cfg.has_target() {
+            return Err(LabeledError::new("scan target is incomplete").with_label(
+                "rows need an address and a port, either as columns or as arguments",
+                target.span,
+            ));
+        }
+        if let Some(udp) = target.udp {
+            cfg.udp = udp;
+        }
+        if let Some(send) = &target.send {
+            cfg.send = Some(send.to_owned());
+        }
+        if let Some(timeout) = target.timeout {
+            cfg.timeout = timeout;
+            cfg.timeout_span = target.span;
+        }
+        let tcp_only =
+            cfg.tls || cfg.http.is_some() || cfg.script.is_some() || !cfg.read_until.is_empty();
+        if cfg.udp && tcp_only || !cfg.udp && cfg.probe.is_some() {
+            return Err(LabeledError::new(
+                "the protocol of this row does not match the given flags",
+            )
+            .with_label(
+                format!(
+                    "this row is scanned over {}",
+                    if cfg.udp { "udp" } else { "tcp" }
+                ),
+                target.span,
+            ));
+        }
+        if target.send.is_some()
+            && (cfg.probe.is_some() || cfg.script.is_some() || cfg.http.is_some())
+        {
+            return Err(LabeledError::new(
+                "`send` cannot be combined with `--probe`, `--script` or `--http`",
+            )
+            .with_label("this row sets `send`", target.span));
+        }
+        Ok(cfg)
     }
 }
 
@@ -37,24 +203,34 @@ impl TryFrom<&EvaluatedCall> for ScanConfig {
     fn try_from(call: &EvaluatedCall) -> Result<Self, Self::Error> {
         let mut builder = ScanConfigBuilder::create_empty();
 
-        let addr = call.req::<String>(0).map_err(|e| {
-            LabeledError::new(e.to_string()).with_label("failed to get target address", call.head)
-        })?;
-        builder.target_address(addr);
-        let port = call.req::<u16>(1).map_err(|e| {
+        // both positionals may come from pipeline input instead
+        builder.target_address(call.opt::<String>(0)?.unwrap_or_default());
+        builder.address_span(call.positional.first().map_or(call.head, Value::span));
+        let port = call.opt::<u16>(1).map_err(|e| {
             LabeledError::new(e.to_string()).with_label("failed to get target port", call.head)
         })?;
-        builder.target_port(port);
+        builder.target_port(port.unwrap_or_default());
 
+        let timing = match call.get_flag::<String>("timing")? {
+            Some(name) => Timing::from_str(&name).map_err(|e| {
+                LabeledError::new(e).with_label(
+                    "unknown timing",
+                    call.get_flag_span("timing").unwrap_or(call.head),
+                )
+            })?,
+            None => Timing::default(),
+        };
+        let profile = timing.profile();
         let timeout: u64 = match call.get_flag_value("timeout") {
             Some(duration) => duration
                 .as_duration()
                 .map_err(|e| LabeledError::new(e.to_string()))?
                 .try_into()
                 .unwrap(),
-            None => DEFAULT_TIMEOUT.as_nanos().try_into().unwrap(),
+            None => profile.timeout.as_nanos().try_into().unwrap(),
         };
         builder.timeout(Duration::from_nanos(timeout));
+        builder.timeout_span(call.get_flag_span("timeout").unwrap_or(call.head));
 
         let send_data = match call.get_flag_value("send") {
             Some(Value::String { val, .. }) => Some(val.chars().map(|i| i as u8).collect()),
@@ -68,20 +244,385 @@ impl TryFrom<&EvaluatedCall> for ScanConfig {
             Some(Value::Filesize { val, .. }) => Some(val.get()),
             _ => None,
         };
+        if receive_byte_count.is_some_and(|count| count <= 0) {
+            return Err(
+                LabeledError::new("`--receive-byte-count` must be at least 1").with_label(
+                    "not positive",
+                    call.get_flag_span("receive-byte-count")
+                        .unwrap_or(call.head),
+                ),
+            );
+        }
         let udp = call.has_flag_or("udp", false);
         builder.udp(udp);
-        if udp && (receive_byte_count.is_none() || !send_data_exists) {
-            return Err(LabeledError::new(
-                "when udp flag is provided receive byte count and send data are mandatory",
-            )
-            .with_label("udp flag is provided but either one of send or receive-byte-count arguments are missing (both are mandatory)", call.get_flag_span("udp").unwrap_or(Span::unknown())));
+        let read_until = read_until_from_call(call)?;
+        if udp && !read_until.is_empty() {
+            return Err(
+                LabeledError::new("read conditions are only available for tcp scans").with_label(
+                    "cannot be combined with `--udp`",
+                    call.get_flag_span("udp").unwrap_or(call.head),
+                ),
+            );
+        }
+        let probe = match call.get_flag::<String>("probe")? {
+            Some(name) => {
+                let span = call.get_flag_span("probe").unwrap_or(call.head);
+                if !udp {
+                    return Err(LabeledError::new(
+                        "protocol probes are only available for udp scans",
+                    )
+                    .with_label("add `--udp` to use this probe", span));
+                }
+                if send_data_exists {
+                    return Err(LabeledError::new(
+                        "`--probe` and `--send` cannot be used together",
+                    )
+                    .with_label("the probe already generates the request payload", span));
+                }
+                Some(
+                    UdpProbe::from_str(&name)
+                        .map_err(|e| LabeledError::new(e).with_label("unknown probe", span))?,
+                )
+            }
+            None => None,
+        };
+        builder.probe(probe);
+
+        let starttls = match call.get_flag::<String>("starttls")? {
+            Some(name) => Some(StartTls::from_str(&name).map_err(|e| {
+                LabeledError::new(e).with_label(
+                    "unknown protocol",
+                    call.get_flag_span("starttls").unwrap_or(call.head),
+                )
+            })?),
+            None => None,
+        };
+        let sni = call.get_flag::<String>("sni")?;
+        // `--sni` and `--starttls` only make sense with a handshake, they imply `--tls`
+        let tls = call.has_flag_or("tls", false) || sni.is_some() || starttls.is_some();
+        if tls && udp {
+            return Err(
+                LabeledError::new("tls is only available for tcp scans").with_label(
+                    "cannot be combined with `--udp`",
+                    call.get_flag_span("udp").unwrap_or(call.head),
+                ),
+            );
+        }
+        builder.tls(tls);
+
+        let script = match call.get_flag_value("script") {
+            Some(value) => {
+                let span = value.span();
+                let steps = value
+                    .as_list()
+                    .map_err(|e| LabeledError::new(e.to_string()))?
+                    .iter()
+                    .map(ScriptStep::try_from)
+                    .collect::<Result<Vec<_>, _>>()?;
+                if udp {
+                    return Err(
+                        LabeledError::new("scripts are only available for tcp scans")
+                            .with_label("cannot be combined with `--udp`", span),
+                    );
+                }
+                if send_data_exists || !read_until.is_empty() {
+                    return Err(LabeledError::new(
+                        "`--script` cannot be combined with `--send` or read conditions",
+                    )
+                    .with_label("the script sends and reads on its own", span));
+                }
+                Some(steps)
+            }
+            None => None,
+        };
+
+        let http = call.get_flag::<String>("http")?;
+        if let Some(span) = call.get_flag_span("http") {
+            if udp {
+                return Err(
+                    LabeledError::new("http probes are only available for tcp scans")
+                        .with_label("cannot be combined with `--udp`", span),
+                );
+            }
+            if send_data_exists
+                || receive_byte_count.is_some()
+                || !read_until.is_empty()
+                || script.is_some()
+            {
+                return Err(LabeledError::new(
+                    "`--http` cannot be combined with `--send`, `--receive-byte-count`, `--script` or read conditions",
+                )
+                .with_label(
+                    "the http probe sends its own request and reads the response",
+                    span,
+                ));
+            }
         }
+        builder.http(http);
+        builder.http_body_bytes(match call.get_flag_value("http-body-bytes") {
+            Some(Value::Int { val, .. }) => val.max(0) as usize,
+            Some(Value::Filesize { val, .. }) => val.get().max(0) as usize,
+            _ => DEFAULT_HTTP_BODY_BYTES,
+        });
+        builder.follow_redirects(call.has_flag_or("follow-redirects", false));
+        builder.sni(sni);
+        builder.starttls(starttls);
+        builder.strict(call.has_flag_or("strict", false));
+        builder.signatures(match call.has_flag_or("detect", false) {
+            true => Some(Arc::new(signatures_from_call(call)?)),
+            false => None,
+        });
         builder.receive_byte_count(receive_byte_count);
+        builder.read_until(read_until);
+        builder.script(script);
+        builder.check(call.get_flag::<Spanned<Closure>>("check")?);
+        builder.retry(retry_from_call(call)?);
+        builder.socket(socket_from_call(call)?);
+        builder.pacing(pacing_from_call(call, &profile)?);
+        builder.schedule(schedule_from_call(call, &profile)?);
+        let top_ports = match call.get_flag::<i64>("top-ports")? {
+            Some(count) if count > 0 => {
+                let available = presets::top_port_list(udp).len();
+                if count as usize > available {
+                    return Err(LabeledError::new(format!(
+                        "`--top-ports` can be at most {} for {}",
+                        available,
+                        if udp { "udp" } else { "tcp" }
+                    ))
+                    .with_label(
+                        format!("only the {} most common ports are known", available),
+                        call.get_flag_span("top-ports").unwrap_or(call.head),
+                    ));
+                }
+                Some(count as usize)
+            }
+            Some(_) => {
+                return Err(
+                    LabeledError::new("`--top-ports` must be at least 1").with_label(
+                        "not positive",
+                        call.get_flag_span("top-ports").unwrap_or(call.head),
+                    ),
+                );
+            }
+            None => None,
+        };
+        let port_profile = call.get_flag::<Spanned<String>>("profile")?;
+        if let (Some(_), Some(port_profile)) = (top_ports, &port_profile) {
+            return Err(
+                LabeledError::new("`--top-ports` and `--profile` cannot be used together")
+                    .with_label(
+                        "pick either a profile or the most common ports",
+                        port_profile.span,
+                    ),
+            );
+        }
+        builder.top_ports(top_ports);
+        builder.profile(port_profile);
+        builder.adaptive(
+            call.has_flag_or("adaptive", false)
+                .then(|| Arc::new(RttTracker::new(profile.min_timeout, profile.max_timeout))),
+        );
+        let flag_span = |flag: &str| call.get_flag_span(flag).unwrap_or(call.head);
+        builder.bind_span(flag_span("bind"));
+        builder.interface_span(flag_span("interface"));
+        builder.ttl_span(flag_span("ttl"));
+        builder.tos_span(call.get_flag_span("tos").unwrap_or(flag_span("dscp")));
+        builder.rst_close_span(flag_span("rst-close"));
         builder.build().map_err(|e| {
             LabeledError::new(format!(
                 "Unable to build config from given arguments: {}",
-                e.to_string()
+                e
             ))
         })
     }
 }
+
+fn read_until_from_call(call: &EvaluatedCall) -> Result<ReadUntil, LabeledError> {
+    let delimiter = match call.get_flag_value("read-until") {
+        Some(Value::String { val, .. }) => Some(val.into_bytes()),
+        Some(Value::Binary { val, .. }) => Some(val),
+        _ => None,
+    };
+    if delimiter.as_ref().is_some_and(Vec::is_empty) {
+        return Err(
+            LabeledError::new("`--read-until` needs a non-empty delimiter").with_label(
+                "empty delimiter",
+                call.get_flag_span("read-until").unwrap_or(call.head),
+            ),
+        );
+    }
+    let regex = match call.get_flag::<String>("read-regex")? {
+        Some(pattern) => Some(Regex::new(&pattern).map_err(|e| {
+            LabeledError::new(format!("cannot compile `--read-regex`: {}", e)).with_label(
+                "invalid regex",
+                call.get_flag_span("read-regex").unwrap_or(call.head),
+            )
+        })?),
+        None => None,
+    };
+    let idle = match call.get_flag_value("read-idle") {
+        Some(duration) => {
+            let nanos = duration
+                .as_duration()
+                .map_err(|e| LabeledError::new(e.to_string()))?;
+            if nanos <= 0 {
+                return Err(
+                    LabeledError::new("`--read-idle` must be a positive duration").with_label(
+                        "not positive",
+                        call.get_flag_span("read-idle").unwrap_or(call.head),
+                    ),
+                );
+            }
+            Some(Duration::from_nanos(nanos as u64))
+        }
+        None => None,
+    };
+    Ok(ReadUntil {
+        delimiter,
+        regex,
+        idle,
+        to_eof: call.has_flag_or("read-to-eof", false),
+    })
+}
+
+/// Built-in signatures, preceded by the ones of `--signatures`
+fn signatures_from_call(call: &EvaluatedCall) -> Result<SignatureDb, LabeledError> {
+    match call.get_flag::<String>("signatures")? {
+        Some(path) => SignatureDb::with_file(Path::new(&path)).map_err(|e| {
+            LabeledError::new(format!("cannot load signature file: {}", e)).with_label(
+                "invalid signature file",
+                call.get_flag_span("signatures").unwrap_or(call.head),
+            )
+        }),
+        None => Ok(SignatureDb::builtin()),
+    }
+}
+
+fn retry_from_call(call: &EvaluatedCall) -> Result<RetryPolicy, LabeledError> {
+    let retries = match call.get_flag::<i64>("retries")? {
+        Some(retries) => u32::try_from(retries).map_err(|_| {
+            LabeledError::new("`--retries` must be a non-negative number").with_label(
+                "invalid retry count",
+                call.get_flag_span("retries").unwrap_or(call.head),
+            )
+        })?,
+        None => 0,
+    };
+    let backoff = match call.get_flag_value("backoff") {
+        Some(duration) => Duration::from_nanos(
+            duration
+                .as_duration()
+                .map_err(|e| LabeledError::new(e.to_string()))?
+                .max(0) as u64,
+        ),
+        None => DEFAULT_BACKOFF,
+    };
+    Ok(RetryPolicy {
+        retries,
+        backoff,
+        exponential: call.has_flag_or("backoff-exponential", false),
+        jitter: call.has_flag_or("backoff-jitter", false),
+    })
+}
+
+fn socket_from_call(call: &EvaluatedCall) -> Result<SocketOptions, LabeledError> {
+    let flag_span = |flag: &str| call.get_flag_span(flag).unwrap_or(call.head);
+    let bind = match call.get_flag::<String>("bind")? {
+        Some(bind) => Some(
+            SocketAddr::from_str(&bind)
+                .or_else(|_| {
+                    IpAddr::from_str(bind.trim_start_matches('[').trim_end_matches(']'))
+                        .map(|ip| SocketAddr::new(ip, 0))
+                })
+                .map_err(|e| {
+                    LabeledError::new(format!("cannot parse bind address: {}", e))
+                        .with_label("expected `ip` or `ip:port`", flag_span("bind"))
+                })?,
+        ),
+        None => None,
+    };
+    let byte_flag = |flag: &str, max: i64| -> Result<Option<u32>, LabeledError> {
+        match call.get_flag::<i64>(flag)? {
+            Some(value) if (0..=max).contains(&value) => Ok(Some(value as u32)),
+            Some(_) => Err(
+                LabeledError::new(format!("`--{}` is out of range", flag)).with_label(
+                    format!("expected a value between 0 and {}", max),
+                    flag_span(flag),
+                ),
+            ),
+            None => Ok(None),
+        }
+    };
+    let ttl = byte_flag("ttl", 255)?;
+    let tos = match (byte_flag("tos", 255)?, byte_flag("dscp", 63)?) {
+        (Some(_), Some(_)) => {
+            return Err(
+                LabeledError::new("`--tos` and `--dscp` cannot be used together").with_label(
+                    "the dscp is the upper six bits of the tos byte",
+                    flag_span("dscp"),
+                ),
+            );
+        }
+        (Some(tos), None) => Some(tos),
+        (None, Some(dscp)) => Some(dscp << 2),
+        (None, None) => None,
+    };
+    Ok(SocketOptions {
+        bind,
+        interface: call.get_flag::<String>("interface")?,
+        ttl,
+        tos,
+        rst_close: call.has_flag_or("rst-close", false),
+    })
+}
+
+fn pacing_from_call(call: &EvaluatedCall, profile: &TimingProfile) -> Result<Pacing, LabeledError> {
+    let rate = match call.get_flag_value("rate") {
+        Some(value) => {
+            let rate = value
+                .coerce_float()
+                .map_err(|e| LabeledError::new(e.to_string()))?;
+            if rate.is_nan() || rate <= 0.0 {
+                return Err(LabeledError::new(
+                    "`--rate` must be a positive number of probes per second",
+                )
+                .with_label("not positive", value.span()));
+            }
+            Some(rate)
+        }
+        None => profile.rate,
+    };
+    let jitter = match call.get_flag_value("jitter") {
+        Some(duration) => Some(Duration::from_nanos(
+            duration
+                .as_duration()
+                .map_err(|e| LabeledError::new(e.to_string()))?
+                .max(0) as u64,
+        )),
+        None => None,
+    };
+    Ok(Pacing::new(rate, jitter))
+}
+
+fn schedule_from_call(
+    call: &EvaluatedCall,
+    profile: &TimingProfile,
+) -> Result<Schedule, LabeledError> {
+    let positive = |flag: &str| -> Result<Option<usize>, LabeledError> {
+        match call.get_flag::<i64>(flag)? {
+            Some(value) if value > 0 => Ok(Some(value as usize)),
+            Some(_) => Err(
+                LabeledError::new(format!("`--{}` must be at least 1", flag)).with_label(
+                    "not positive",
+                    call.get_flag_span(flag).unwrap_or(call.head),
+                ),
+            ),
+            None => Ok(None),
+        }
+    };
+    Ok(Schedule {
+        parallel: positive("parallel")?.unwrap_or(profile.parallel),
+        max_per_host: positive("max-per-host")?,
+        randomize: call.has_flag_or("randomize", false),
+    })
+}
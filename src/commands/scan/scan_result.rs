@@ -1,28 +1,144 @@
 use std::time::Duration;
 
 use derive_builder::Builder;
-use nu_protocol::record;
+use nu_protocol::{Value, record};
 
 use crate::helpers::AsValue;
 
+use super::{
+    detect::Detection, http::HttpReport, receive::ReadEnd, script::ScriptReport, tls::TlsReport,
+};
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum PortState {
+    Open,
+    #[default]
+    Closed,
+    /// No answer was received, the port is either open or a firewall dropped the probe (udp only)
+    OpenFiltered,
+}
+
+impl AsValue for PortState {
+    fn as_value(self, span: nu_protocol::Span) -> nu_protocol::Value {
+        match self {
+            PortState::Open => "open",
+            PortState::Closed => "closed",
+            PortState::OpenFiltered => "open|filtered",
+        }
+        .as_value(span)
+    }
+}
+
 #[derive(Default, Clone, Builder, Debug)]
 pub(super) struct ScanResult<T: AsValue + ToString> {
     address: T,
     port: u16,
-    is_open: bool,
+    state: PortState,
     elapsed: Duration,
+    /// Duration of the tcp handshake
+    #[builder(default)]
+    connect_time: Option<Duration>,
+    /// Time spent writing the payload
+    #[builder(default)]
+    send_time: Option<Duration>,
+    /// From the end of the send (or of the handshake) to the first byte of the response
+    #[builder(default)]
+    first_byte_time: Option<Duration>,
+    /// Whole scan of the target: every attempt, the pauses between them and service detection
+    #[builder(default)]
+    total_time: Duration,
     received_data: Option<Vec<u8>>,
+    #[builder(default)]
+    read_end: Option<ReadEnd>,
+    /// Timeout derived by `--adaptive`
+    #[builder(default)]
+    timeout: Option<Duration>,
+    /// Elapsed time of every attempt, only set when retries are enabled
+    #[builder(default)]
+    attempts: Option<Vec<Duration>>,
+    #[builder(default)]
+    error: Option<String>,
+    #[builder(default)]
+    error_kind: Option<String>,
+    #[builder(default)]
+    detection: Option<Detection>,
+    #[builder(default)]
+    probe_result: Option<Value>,
+    #[builder(default)]
+    tls: Option<TlsReport>,
+    #[builder(default)]
+    http: Option<HttpReport>,
+    #[builder(default)]
+    script: Option<ScriptReport>,
+}
+
+impl<T: AsValue + ToString> ScanResult<T> {
+    pub fn state(&self) -> PortState {
+        self.state
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn connect_time(&self) -> Option<Duration> {
+        self.connect_time
+    }
+
+    pub fn first_byte_time(&self) -> Option<Duration> {
+        self.first_byte_time
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn error_kind(&self) -> Option<&str> {
+        self.error_kind.as_deref()
+    }
 }
 
 impl<T: AsValue + ToString> AsValue for ScanResult<T> {
     fn as_value(self, span: nu_protocol::Span) -> nu_protocol::Value {
-        record! {
+        let mut result = record! {
             "address" => self.address.as_value(span),
             "port" => self.port.as_value(span),
-            "is_open"=> self.is_open.as_value(span),
+            "is_open"=> (self.state == PortState::Open).as_value(span),
+            "state" => self.state.as_value(span),
             "elapsed" =>  self.elapsed.as_value(span),
+            "connect_time" => self.connect_time.as_value(span),
+            "send_time" => self.send_time.as_value(span),
+            "first_byte_time" => self.first_byte_time.as_value(span),
+            "total_time" => self.total_time.as_value(span),
             "received_data" =>  self.received_data.as_value(span),
+            "error" => self.error.as_value(span),
+            "error_kind" => self.error_kind.as_value(span),
+        };
+        if let Some(attempts) = self.attempts {
+            result.insert("attempts", Value::int(attempts.len() as i64, span));
+            result.insert("attempt_elapsed", attempts.as_value(span));
         }
-        .as_value(span)
+        if let Some(timeout) = self.timeout {
+            result.insert("timeout", timeout.as_value(span));
+        }
+        if let Some(read_end) = self.read_end {
+            result.insert("read_end", read_end.as_value(span));
+        }
+        if let Some(detection) = self.detection {
+            detection.write_to(&mut result, span);
+        }
+        if let Some(probe_result) = self.probe_result {
+            result.insert("probe_result", probe_result);
+        }
+        if let Some(tls) = self.tls {
+            result.insert("tls", tls.as_value(span));
+        }
+        if let Some(http) = self.http {
+            result.insert("http", http.as_value(span));
+        }
+        if let Some(script) = self.script {
+            result.insert("script", script.as_value(span));
+        }
+        result.as_value(span)
     }
 }
@@ -0,0 +1,319 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::{Arc, Condvar, Mutex, mpsc},
+    thread,
+};
+
+use nu_protocol::{Record, Span, Value};
+
+use super::{interrupt::Interrupt, scan_config::ScanConfig};
+
+/// Targets `--randomize` reads ahead to pick the next one from, so long streams start right away
+const SHUFFLE_WINDOW: usize = 4096;
+
+/// Scan of one pipeline row, ready to run
+pub(super) struct Job {
+    pub cfg: ScanConfig,
+    /// Input columns copied to the result
+    pub columns: Record,
+    pub span: Span,
+}
+
+/// Order and concurrency of the scans of piped targets
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Schedule {
+    /// Number of targets scanned at the same time
+    pub parallel: usize,
+    /// Limit of simultaneous scans of the same address
+    pub max_per_host: Option<usize>,
+    /// Scan the targets in random order
+    pub randomize: bool,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            parallel: 1,
+            max_per_host: None,
+            randomize: false,
+        }
+    }
+}
+
+type Jobs = Box<dyn Iterator<Item = Result<Job, Value>> + Send>;
+
+/// Runs `work` on every job following `schedule`. Rows that failed to become a job are passed
+/// through as they are. Results come in input order when scanning one target at a time and in
+/// completion order otherwise. Workers stop picking up jobs once interrupted.
+pub(super) fn run<W>(
+    schedule: Schedule,
+    interrupt: Interrupt,
+    jobs: impl Iterator<Item = Result<Job, Value>> + Send + 'static,
+    work: W,
+) -> Box<dyn Iterator<Item = Value> + Send>
+where
+    W: Fn(Job) -> Value + Send + Sync + 'static,
+{
+    let jobs: Jobs = match schedule.randomize {
+        true => Box::new(shuffled(jobs, SHUFFLE_WINDOW)),
+        false => Box::new(jobs),
+    };
+    if schedule.parallel <= 1 {
+        return Box::new(jobs.map(move |job| match job {
+            Ok(job) => work(job),
+            Err(value) => value,
+        }));
+    }
+
+    let queue = Arc::new(Queue {
+        state: Mutex::new(QueueState {
+            source: Some(jobs),
+            pending: VecDeque::new(),
+            active: HashMap::new(),
+        }),
+        changed: Condvar::new(),
+        max_per_host: schedule.max_per_host.unwrap_or(usize::MAX),
+    });
+    let work = Arc::new(work);
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..schedule.parallel {
+        let queue = queue.clone();
+        let work = work.clone();
+        let sender = sender.clone();
+        let interrupt = interrupt.clone();
+        thread::spawn(move || {
+            while !interrupt.triggered()
+                && let Some(next) = queue.next()
+            {
+                let value = match next {
+                    Next::Run(job, host) => {
+                        let value = work(*job);
+                        queue.finish(host);
+                        value
+                    }
+                    Next::Ready(value) => value,
+                };
+                if sender.send(value).is_err() {
+                    // the output stream was dropped, nobody waits for more results
+                    return;
+                }
+            }
+        });
+    }
+    Box::new(receiver.into_iter())
+}
+
+/// Yields the items in random order, each picked among the next `window` items of the input.
+/// Inputs no longer than the window are shuffled entirely.
+fn shuffled<T>(mut items: impl Iterator<Item = T>, window: usize) -> impl Iterator<Item = T> {
+    let mut buffer = Vec::with_capacity(window);
+    std::iter::from_fn(move || {
+        buffer.extend(items.by_ref().take(window - buffer.len()));
+        if buffer.is_empty() {
+            return None;
+        }
+        let pick = rand::random_range(0..buffer.len());
+        Some(buffer.swap_remove(pick))
+    })
+}
+
+enum Next {
+    Run(Box<Job>, Option<IpAddr>),
+    Ready(Value),
+}
+
+struct QueueState {
+    source: Option<Jobs>,
+    /// Jobs read ahead while their host was busy
+    pending: VecDeque<(Job, Option<IpAddr>)>,
+    active: HashMap<IpAddr, usize>,
+}
+
+struct Queue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+    max_per_host: usize,
+}
+
+impl Queue {
+    /// Next job whose host has room for another scan, waits while every remaining job is blocked
+    fn next(&self) -> Option<Next> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let position = state
+                .pending
+                .iter()
+                .position(|(_, host)| self.has_room(&state.active, *host));
+            if let Some(position) = position {
+                let (job, host) = state.pending.remove(position).unwrap();
+                return Some(self.start(&mut state, job, host));
+            }
+            match state.source.as_mut().map(Iterator::next) {
+                Some(Some(Ok(job))) => {
+                    let host = job.cfg.get_socket_addr().ok().map(|address| address.ip());
+                    match self.has_room(&state.active, host) {
+                        true => return Some(self.start(&mut state, job, host)),
+                        false => state.pending.push_back((job, host)),
+                    }
+                }
+                Some(Some(Err(value))) => return Some(Next::Ready(value)),
+                Some(None) => state.source = None,
+                None if state.pending.is_empty() => return None,
+                None => state = self.changed.wait(state).unwrap(),
+            }
+        }
+    }
+
+    fn has_room(&self, active: &HashMap<IpAddr, usize>, host: Option<IpAddr>) -> bool {
+        host.is_none_or(|host| active.get(&host).copied().unwrap_or(0) < self.max_per_host)
+    }
+
+    fn start(&self, state: &mut QueueState, job: Job, host: Option<IpAddr>) -> Next {
+        if let Some(host) = host {
+            *state.active.entry(host).or_default() += 1;
+        }
+        Next::Run(Box::new(job), host)
+    }
+
+    fn finish(&self, host: Option<IpAddr>) {
+        if let Some(host) = host {
+            let mut state = self.state.lock().unwrap();
+            if let Some(count) = state.active.get_mut(&host) {
+                *count -= 1;
+            }
+        }
+        self.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    fn job(host: u8, port: u16) -> Result<Job, Value> {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, host)), port);
+        Ok(Job {
+            cfg: ScanConfig::for_target(address, false, Duration::from_secs(1)),
+            columns: Record::new(),
+            span: Span::test_data(),
+        })
+    }
+
+    /// Runs jobs that take a while, returns the most scans seen at once of one host and in total
+    fn peak_per_host(schedule: Schedule, jobs: Vec<Result<Job, Value>>) -> (usize, usize) {
+        let active = Arc::new(Mutex::new(HashMap::<IpAddr, usize>::new()));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak_total = Arc::new(AtomicUsize::new(0));
+        let work = {
+            let (active, peak, running, peak_total) = (
+                active.clone(),
+                peak.clone(),
+                running.clone(),
+                peak_total.clone(),
+            );
+            move |job: Job| {
+                let host = job.cfg.get_socket_addr().unwrap().ip();
+                {
+                    let mut active = active.lock().unwrap();
+                    let count = active.entry(host).or_default();
+                    *count += 1;
+                    peak.fetch_max(*count, Ordering::SeqCst);
+                }
+                let total = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak_total.fetch_max(total, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+                *active.lock().unwrap().get_mut(&host).unwrap() -= 1;
+                Value::test_int(i64::from(job.cfg.target_port().to_owned()))
+            }
+        };
+        let results = run(schedule, Interrupt::default(), jobs.into_iter(), work).count();
+        assert_eq!(results, 8);
+        (
+            peak.load(Ordering::SeqCst),
+            peak_total.load(Ordering::SeqCst),
+        )
+    }
+
+    #[test]
+    fn max_per_host_caps_scans_of_one_address() {
+        let schedule = Schedule {
+            parallel: 4,
+            max_per_host: Some(1),
+            randomize: false,
+        };
+        // the jobs of the second host wait behind the first host's in the input
+        let jobs = (0..4)
+            .map(|port| job(1, port))
+            .chain((0..4).map(|port| job(2, port)))
+            .collect();
+        let (per_host, total) = peak_per_host(schedule, jobs);
+        assert_eq!(per_host, 1);
+        assert_eq!(total, 2);
+    }
+
+    #[test]
+    fn without_a_cap_a_host_gets_every_worker() {
+        let schedule = Schedule {
+            parallel: 4,
+            max_per_host: None,
+            randomize: false,
+        };
+        let (per_host, _) = peak_per_host(schedule, (0..8).map(|port| job(1, port)).collect());
+        assert_eq!(per_host, 4);
+    }
+
+    #[test]
+    fn sequential_scans_keep_the_input_order() {
+        let jobs = vec![job(1, 1), Err(Value::test_string("bad row")), job(1, 2)];
+        let results = run(
+            Schedule::default(),
+            Interrupt::default(),
+            jobs.into_iter(),
+            |job| Value::test_int(i64::from(job.cfg.target_port().to_owned())),
+        )
+        .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                Value::test_int(1),
+                Value::test_string("bad row"),
+                Value::test_int(2)
+            ]
+        );
+    }
+
+    #[test]
+    fn shuffle_keeps_every_item() {
+        let mut items = shuffled(0..1000, 64).collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(items, (0..1000).collect::<Vec<_>>());
+        assert_eq!(shuffled(0..0, 64).count(), 0);
+    }
+
+    #[test]
+    fn shuffle_reads_at_most_a_window_ahead() {
+        let read = Arc::new(AtomicUsize::new(0));
+        let counted = {
+            let read = read.clone();
+            (0..).inspect(move |_| {
+                read.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+        let mut items = shuffled(counted, 16);
+        for taken in 1..=100 {
+            let item = items.next().unwrap();
+            // the item was among those read so far, which never exceed a window
+            assert!(item < 15 + taken, "{item}");
+            assert_eq!(read.load(Ordering::SeqCst), 15 + taken);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use nu_protocol::{LabeledError, Span, Value, record};
+
+use crate::helpers::AsValue;
+
+use super::{
+    connection::Connection,
+    receive::{ReadEnd, ReadUntil, receive_buffered},
+};
+
+/// One step of a `--script` dialogue, the data is sent first then the expectation is awaited
+#[derive(Default, Clone, Debug)]
+pub(super) struct ScriptStep {
+    send: Option<Vec<u8>>,
+    expect: Option<Vec<u8>>,
+    timeout: Option<Duration>,
+}
+
+impl TryFrom<&Value> for ScriptStep {
+    type Error = LabeledError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let span = value.span();
+        let record = value.as_record().map_err(|_| {
+            LabeledError::new("script steps must be records")
+                .with_label("expected a record with `send`, `expect` or `timeout`", span)
+        })?;
+        let mut step = ScriptStep::default();
+        for (key, value) in record.iter() {
+            match key.as_str() {
+                "send" => step.send = Some(bytes_of(value)?),
+                "expect" => step.expect = Some(bytes_of(value)?),
+                "timeout" => {
+                    let nanos = value
+                        .as_duration()
+                        .map_err(|e| LabeledError::new(e.to_string()))?;
+                    step.timeout = Some(Duration::from_nanos(nanos.max(0) as u64));
+                }
+                other => {
+                    return Err(LabeledError::new(format!(
+                        "unknown script step field `{}`",
+                        other
+                    ))
+                    .with_label("expected `send`, `expect` or `timeout`", value.span()));
+                }
+            }
+        }
+        if step.send.is_none() && step.expect.is_none() {
+            return Err(LabeledError::new("script step does nothing")
+                .with_label("add `send` and/or `expect` to this step", span));
+        }
+        if step.expect.as_ref().is_some_and(Vec::is_empty) {
+            return Err(
+                LabeledError::new("`expect` cannot be empty").with_label("empty expectation", span)
+            );
+        }
+        Ok(step)
+    }
+}
+
+fn bytes_of(value: &Value) -> Result<Vec<u8>, LabeledError> {
+    match value {
+        Value::String { val, .. } => Ok(val.as_bytes().to_vec()),
+        Value::Binary { val, .. } => Ok(val.clone()),
+        other => Err(LabeledError::new("expected a string or binary")
+            .with_label(format!("got {}", other.get_type()), other.span())),
+    }
+}
+
+/// What happened in a single step of the dialogue
+#[derive(Clone, Debug)]
+struct Exchange {
+    step: usize,
+    sent: Option<Vec<u8>>,
+    received: Option<Vec<u8>>,
+    read_end: Option<ReadEnd>,
+}
+
+impl AsValue for Exchange {
+    fn as_value(self, span: Span) -> Value {
+        record! {
+            "step" => Value::int(self.step as i64, span),
+            "sent" => self.sent.map(|data| text_or_binary(data, span)).as_value(span),
+            "received" => self.received.map(|data| text_or_binary(data, span)).as_value(span),
+            "read_end" => self.read_end.as_value(span),
+        }
+        .as_value(span)
+    }
+}
+
+fn text_or_binary(data: Vec<u8>, span: Span) -> Value {
+    match String::from_utf8(data) {
+        Ok(text) => Value::string(text, span),
+        Err(err) => Value::binary(err.into_bytes(), span),
+    }
+}
+
+/// Outcome of a `--script` dialogue, reported in the `script` column
+#[derive(Default, Clone, Debug)]
+pub(super) struct ScriptReport {
+    failed_step: Option<usize>,
+    error: Option<String>,
+    transcript: Vec<Exchange>,
+}
+
+impl ScriptReport {
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Everything received over the dialogue, used as `received_data`
+    pub fn received(&self) -> Vec<u8> {
+        self.transcript
+            .iter()
+            .filter_map(|exchange| exchange.received.as_deref())
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    fn fail(&mut self, step: usize, error: String) {
+        self.failed_step = Some(step);
+        self.error = Some(format!("step {}: {}", step, error));
+    }
+}
+
+impl AsValue for ScriptReport {
+    fn as_value(self, span: Span) -> Value {
+        record! {
+            "passed" => self.error.is_none().as_value(span),
+            "failed_step" => self.failed_step.map(|step| Value::int(step as i64, span)).as_value(span),
+            "error" => self.error.as_value(span),
+            "transcript" => self.transcript.as_value(span),
+        }
+        .as_value(span)
+    }
+}
+
+/// Runs `steps` in order over `connection` and stops at the first one that fails.
+/// Bytes received past an expectation are kept for the following steps.
+pub(super) fn run(
+    connection: &mut dyn Connection,
+    steps: &[ScriptStep],
+    limit: usize,
+    timeout: Duration,
+) -> ScriptReport {
+    let mut report = ScriptReport::default();
+    let mut pending = vec![];
+    for (index, step) in steps.iter().enumerate() {
+        let mut exchange = Exchange {
+            step: index,
+            sent: step.send.clone(),
+            received: None,
+            read_end: None,
+        };
+        if let Some(data) = &step.send
+            && let Err(err) = connection.write_all(data)
+        {
+            report.transcript.push(exchange);
+            report.fail(index, format!("cannot send data: {}", err));
+            return report;
+        }
+        let Some(expect) = &step.expect else {
+            report.transcript.push(exchange);
+            continue;
+        };
+        let until = ReadUntil {
+            delimiter: Some(expect.clone()),
+            ..Default::default()
+        };
+        let timeout = step.timeout.unwrap_or(timeout);
+        match receive_buffered(connection, &mut pending, &until, limit, timeout) {
+            Ok((data, end, _)) => {
+                exchange.received = Some(data);
+                exchange.read_end = Some(end);
+                report.transcript.push(exchange);
+                if end != ReadEnd::Delimiter {
+                    let error = format!(
+                        "expected `{}` but the read ended with {}",
+                        String::from_utf8_lossy(expect).escape_debug(),
+                        end
+                    );
+                    report.fail(index, error);
+                    return report;
+                }
+            }
+            Err(err) => {
+                report.transcript.push(exchange);
+                report.fail(index, err.to_string());
+                return report;
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use crate::commands::scan::interrupt::{Interrupt, SignalStream};
+
+    use super::*;
+
+    fn step(send: Option<&str>, expect: Option<&str>) -> ScriptStep {
+        ScriptStep {
+            send: send.map(|data| data.as_bytes().to_vec()),
+            expect: expect.map(|data| data.as_bytes().to_vec()),
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn responses_in_one_write_reach_their_steps() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 6];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(&request, b"PING\r\n");
+            stream.write_all(b"+ONE\r\n+TWO\r\n").unwrap();
+            // keep the connection open until the client is done
+            let _ = stream.read(&mut request);
+        });
+        let mut connection =
+            SignalStream::new(TcpStream::connect(address).unwrap(), Interrupt::default());
+        let steps = [
+            step(Some("PING\r\n"), Some("+ONE\r\n")),
+            step(None, Some("+TWO\r\n")),
+        ];
+        let report = run(&mut connection, &steps, 1024, Duration::from_secs(5));
+        drop(connection);
+        server.join().unwrap();
+
+        assert_eq!(report.error(), None);
+        assert_eq!(report.received(), b"+ONE\r\n+TWO\r\n");
+        assert_eq!(
+            report.transcript[1].received.as_deref(),
+            Some(&b"+TWO\r\n"[..])
+        );
+    }
+
+    #[test]
+    fn missing_expectation_fails_the_step() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"+ONE\r\n").unwrap();
+        });
+        let mut connection =
+            SignalStream::new(TcpStream::connect(address).unwrap(), Interrupt::default());
+        let steps = [step(None, Some("+ONE\r\n")), step(None, Some("+TWO\r\n"))];
+        let report = run(&mut connection, &steps, 1024, Duration::from_secs(5));
+        server.join().unwrap();
+
+        assert_eq!(report.failed_step, Some(1));
+        assert_eq!(
+            report.error(),
+            Some("step 1: expected `+TWO\\r\\n` but the read ended with eof")
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+use super::{
+    interrupt::{Interrupt, SignalStream},
+    probe_error::{ProbeError, ProbeErrorKind},
+};
+
+/// Argument responsible for a socket setting, used to point `--strict` errors at it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum SocketSetting {
+    Timeout,
+    Bind,
+    Interface,
+    Ttl,
+    Tos,
+    RstClose,
+}
+
+/// How probe sockets are created
+#[derive(Default, Clone, Debug)]
+pub(super) struct SocketOptions {
+    /// Local address (and optionally port) the probe is sent from
+    pub bind: Option<SocketAddr>,
+    /// Network device the socket is bound to (`SO_BINDTODEVICE`)
+    pub interface: Option<String>,
+    pub ttl: Option<u32>,
+    /// Type of service (ipv4) or traffic class (ipv6) byte
+    pub tos: Option<u32>,
+    /// Close with `SO_LINGER` 0, resetting the connection instead of leaving a `TIME_WAIT` socket
+    pub rst_close: bool,
+}
+
+impl SocketOptions {
+    /// Creates a socket for `target` with every option applied and bound to the local address
+    fn socket(
+        &self,
+        target: SocketAddr,
+        kind: Type,
+        protocol: Protocol,
+    ) -> Result<Socket, ProbeError> {
+        let socket = Socket::new(Domain::for_address(target), kind, Some(protocol))
+            .map_err(|e| ProbeError::new(ProbeErrorKind::Bind, e))?;
+        if let Some(interface) = &self.interface {
+            bind_device(&socket, interface)
+                .map_err(|e| ProbeError::socket_option(SocketSetting::Interface, e))?;
+        }
+        if let Some(ttl) = self.ttl {
+            match target {
+                SocketAddr::V4(_) => socket.set_ttl_v4(ttl),
+                SocketAddr::V6(_) => socket.set_unicast_hops_v6(ttl),
+            }
+            .map_err(|e| ProbeError::socket_option(SocketSetting::Ttl, e))?;
+        }
+        if let Some(tos) = self.tos {
+            match target {
+                SocketAddr::V4(_) => socket.set_tos_v4(tos),
+                SocketAddr::V6(_) => set_tclass(&socket, tos),
+            }
+            .map_err(|e| ProbeError::socket_option(SocketSetting::Tos, e))?;
+        }
+        if self.rst_close {
+            socket
+                .set_linger(Some(Duration::ZERO))
+                .map_err(|e| ProbeError::socket_option(SocketSetting::RstClose, e))?;
+        }
+        if let Some(bind) = self.bind {
+            if bind.is_ipv4() != target.is_ipv4() {
+                return Err(ProbeError::bind(
+                    Some(SocketSetting::Bind),
+                    io::Error::new(
+                        ErrorKind::InvalidInput,
+                        "the bind address and the target use different address families",
+                    ),
+                ));
+            }
+            // a fixed source port has to be reusable while the previous probe is in TIME_WAIT
+            if bind.port() != 0 {
+                socket
+                    .set_reuse_address(true)
+                    .map_err(|e| ProbeError::socket_option(SocketSetting::Bind, e))?;
+            }
+            socket
+                .bind(&SockAddr::from(bind))
+                .map_err(|e| ProbeError::bind(Some(SocketSetting::Bind), e))?;
+        }
+        Ok(socket)
+    }
+
+    /// Connects a tcp socket to `target`, the wait can be interrupted
+    pub fn connect_tcp(
+        &self,
+        target: SocketAddr,
+        timeout: Duration,
+        interrupt: &Interrupt,
+    ) -> Result<SignalStream, ProbeError> {
+        let socket = self.socket(target, Type::STREAM, Protocol::TCP)?;
+        interrupt
+            .connect(&socket, &SockAddr::from(target), timeout)
+            .map_err(|e| ProbeError::from_io(ProbeErrorKind::Connect, e))?;
+        Ok(SignalStream::new(socket.into(), interrupt.clone()))
+    }
+
+    /// Creates a udp socket for `target`, bound to the wildcard address unless `bind` is set
+    pub fn bind_udp(&self, target: SocketAddr) -> Result<UdpSocket, ProbeError> {
+        let socket = self.socket(target, Type::DGRAM, Protocol::UDP)?;
+        if self.bind.is_none() {
+            let wildcard: SocketAddr = match target {
+                SocketAddr::V4(_) => "0.0.0.0:0",
+                SocketAddr::V6(_) => "[::]:0",
+            }
+            .parse()
+            .unwrap();
+            socket
+                .bind(&SockAddr::from(wildcard))
+                .map_err(|e| ProbeError::bind(None, e))?;
+        }
+        Ok(socket.into())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_device(socket: &Socket, interface: &str) -> io::Result<()> {
+    socket.bind_device(Some(interface.as_bytes()))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn bind_device(_socket: &Socket, _interface: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "binding to an interface is only supported on linux",
+    ))
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn set_tclass(socket: &Socket, tclass: u32) -> io::Result<()> {
+    socket.set_tclass_v6(tclass)
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn set_tclass(_socket: &Socket, _tclass: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "the traffic class of ipv6 sockets cannot be set on this platform",
+    ))
+}
@@ -0,0 +1,106 @@
+use std::{net::SocketAddr, str::FromStr, time::Duration};
+
+use nu_protocol::{LabeledError, Record, Span, Value};
+
+/// Scan target read from a pipeline row, every field it sets overrides the matching flag
+#[derive(Default, Clone, Debug)]
+pub(super) struct Target {
+    pub address: Option<String>,
+    pub port: Option<u16>,
+    pub udp: Option<bool>,
+    pub send: Option<Vec<u8>>,
+    pub timeout: Option<Duration>,
+    pub span: Span,
+    /// Input columns copied to the result so it can be joined back
+    pub columns: Record,
+}
+
+impl TryFrom<Value> for Target {
+    type Error = LabeledError;
+
+    fn try_from(row: Value) -> Result<Self, Self::Error> {
+        let span = row.span();
+        let mut target = Target {
+            span,
+            ..Default::default()
+        };
+        match row {
+            Value::String { val, .. } => {
+                let (address, port) = split_host_port(&val)
+                    .map_err(|e| LabeledError::new(e).with_label("invalid target", span))?;
+                target.address = Some(address);
+                target.port = port;
+            }
+            Value::Int { val, .. } => target.port = Some(port_of(val, span)?),
+            Value::Record { val, .. } => {
+                let record = val.into_owned();
+                // `port list` rows carry `local_address`, `local_port` and `type`
+                target.address = match ["address", "host", "local_address"]
+                    .iter()
+                    .find_map(|column| record.get(column))
+                {
+                    Some(address) => Some(address.coerce_string()?),
+                    None => None,
+                };
+                target.port = ["port", "local_port"]
+                    .iter()
+                    .find_map(|column| record.get(column))
+                    .map(|value| port_of(value.as_int()?, value.span()))
+                    .transpose()?;
+                target.udp = match (record.get("udp"), record.get("type")) {
+                    (Some(udp), _) => Some(udp.as_bool()?),
+                    (None, Some(Value::String { val, .. })) => {
+                        Some(val.eq_ignore_ascii_case("udp"))
+                    }
+                    _ => None,
+                };
+                target.send = match record.get("send") {
+                    Some(Value::String { val, .. }) => Some(val.chars().map(|i| i as u8).collect()),
+                    Some(Value::Binary { val, .. }) => Some(val.to_owned()),
+                    Some(Value::Nothing { .. }) | None => None,
+                    Some(other) => {
+                        return Err(LabeledError::new("`send` must be a string or binary")
+                            .with_label(format!("got {}", other.get_type()), other.span()));
+                    }
+                };
+                target.timeout = match record.get("timeout") {
+                    Some(Value::Nothing { .. }) | None => None,
+                    Some(timeout) => {
+                        Some(Duration::from_nanos(timeout.as_duration()?.max(0) as u64))
+                    }
+                };
+                target.columns = record;
+            }
+            other => {
+                return Err(LabeledError::new(
+                    "scan targets must be records, `host:port` strings or port numbers",
+                )
+                .with_label(format!("got {}", other.get_type()), span));
+            }
+        }
+        Ok(target)
+    }
+}
+
+fn port_of(value: i64, span: Span) -> Result<u16, LabeledError> {
+    u16::try_from(value)
+        .map_err(|_| LabeledError::new("invalid port").with_label("not a valid port number", span))
+}
+
+/// Splits `host:port`, `[v6]:port` or a bare address (without port)
+fn split_host_port(target: &str) -> Result<(String, Option<u16>), String> {
+    if let Ok(address) = SocketAddr::from_str(target) {
+        return Ok((address.ip().to_string(), Some(address.port())));
+    }
+    // bare ipv6 addresses contain colons too
+    if target.matches(':').count() > 1 || target.starts_with('[') {
+        return Ok((target.to_string(), None));
+    }
+    match target.rsplit_once(':') {
+        Some((host, port)) => port
+            .parse()
+            .map(|port| (host.to_string(), Some(port)))
+            .map_err(|_| format!("invalid port `{}` in `{}`", port, target)),
+        None => Ok((target.to_string(), None)),
+    }
+}
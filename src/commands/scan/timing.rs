@@ -0,0 +1,147 @@
+use std::{str::FromStr, time::Duration};
+
+/// Presets bundling the pace of a scan, modeled after nmap's `-T` templates
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Timing {
+    Paranoid,
+    Polite,
+    #[default]
+    Normal,
+    Aggressive,
+    Insane,
+}
+
+/// Values a timing preset provides for the flags that were not given
+#[derive(Clone, Copy, Debug)]
+pub(super) struct TimingProfile {
+    pub timeout: Duration,
+    /// Probes per second
+    pub rate: Option<f64>,
+    pub parallel: usize,
+    /// Bounds of the timeouts derived by `--adaptive`
+    pub min_timeout: Duration,
+    pub max_timeout: Duration,
+}
+
+impl Timing {
+    pub const NAMES: &[&str] = &["paranoid", "polite", "normal", "aggressive", "insane"];
+
+    pub fn profile(&self) -> TimingProfile {
+        let ms = Duration::from_millis;
+        match self {
+            Timing::Paranoid => TimingProfile {
+                timeout: ms(5000),
+                rate: Some(1.0 / 300.0),
+                parallel: 1,
+                min_timeout: ms(100),
+                max_timeout: ms(10_000),
+            },
+            Timing::Polite => TimingProfile {
+                timeout: ms(2000),
+                rate: Some(2.5),
+                parallel: 1,
+                min_timeout: ms(100),
+                max_timeout: ms(10_000),
+            },
+            Timing::Normal => TimingProfile {
+                timeout: ms(1000),
+                rate: None,
+                parallel: 1,
+                min_timeout: ms(100),
+                max_timeout: ms(10_000),
+            },
+            Timing::Aggressive => TimingProfile {
+                timeout: ms(500),
+                rate: None,
+                parallel: 64,
+                min_timeout: ms(100),
+                max_timeout: ms(1250),
+            },
+            Timing::Insane => TimingProfile {
+                timeout: ms(250),
+                rate: None,
+                parallel: 256,
+                min_timeout: ms(50),
+                max_timeout: ms(300),
+            },
+        }
+    }
+}
+
+impl FromStr for Timing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "paranoid" => Ok(Timing::Paranoid),
+            "polite" => Ok(Timing::Polite),
+            "normal" => Ok(Timing::Normal),
+            "aggressive" => Ok(Timing::Aggressive),
+            "insane" => Ok(Timing::Insane),
+            other => Err(format!(
+                "unknown timing `{}`, expected one of: {}",
+                other,
+                Timing::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRESETS: [Timing; 5] = [
+        Timing::Paranoid,
+        Timing::Polite,
+        Timing::Normal,
+        Timing::Aggressive,
+        Timing::Insane,
+    ];
+
+    #[test]
+    fn names_parse_to_their_preset() {
+        for (name, timing) in Timing::NAMES.iter().zip(PRESETS) {
+            assert_eq!(Timing::from_str(name), Ok(timing));
+        }
+        assert_eq!(Timing::from_str("Aggressive"), Ok(Timing::Aggressive));
+        assert_eq!(Timing::default(), Timing::Normal);
+    }
+
+    #[test]
+    fn unknown_name_lists_the_presets() {
+        assert_eq!(
+            Timing::from_str("ludicrous"),
+            Err("unknown timing `ludicrous`, expected one of: paranoid, polite, normal, aggressive, insane".into())
+        );
+    }
+
+    #[test]
+    fn normal_keeps_the_defaults() {
+        let profile = Timing::Normal.profile();
+        assert_eq!(profile.timeout, Duration::from_secs(1));
+        assert_eq!(profile.rate, None);
+        assert_eq!(profile.parallel, 1);
+    }
+
+    #[test]
+    fn faster_presets_are_faster() {
+        for pair in PRESETS.windows(2) {
+            let (slower, faster) = (pair[0].profile(), pair[1].profile());
+            assert!(faster.timeout < slower.timeout, "{:?}", pair);
+            assert!(faster.parallel >= slower.parallel, "{:?}", pair);
+            assert!(faster.max_timeout <= slower.max_timeout, "{:?}", pair);
+            let rate = |profile: TimingProfile| profile.rate.unwrap_or(f64::INFINITY);
+            assert!(rate(faster) >= rate(slower), "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn adaptive_bounds_contain_the_timeout() {
+        for timing in PRESETS {
+            let profile = timing.profile();
+            assert!(profile.min_timeout <= profile.timeout, "{:?}", timing);
+            assert!(profile.timeout <= profile.max_timeout, "{:?}", timing);
+        }
+    }
+}
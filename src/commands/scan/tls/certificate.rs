@@ -0,0 +1,112 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use nu_protocol::{Record, Span, record};
+use x509_parser::{
+    extensions::GeneralName,
+    objects::{oid_registry, oid2sn},
+    prelude::{FromDer, X509Certificate},
+    public_key::PublicKey,
+};
+
+use crate::helpers::AsValue;
+
+const OID_ED25519: &str = "1.3.101.112";
+const OID_ED448: &str = "1.3.101.113";
+
+/// Audit relevant fields of a peer certificate
+#[derive(Clone, Debug)]
+pub(super) struct CertificateInfo {
+    subject: String,
+    issuer: String,
+    sans: Vec<String>,
+    serial: String,
+    not_before: Option<DateTime<FixedOffset>>,
+    not_after: Option<DateTime<FixedOffset>>,
+    days_until_expiry: Option<i64>,
+    key_type: String,
+    key_size: i64,
+    signature_algorithm: String,
+    self_signed: bool,
+}
+
+impl CertificateInfo {
+    pub fn parse(der: &[u8]) -> Result<Self, String> {
+        let (_, cert) = X509Certificate::from_der(der).map_err(|e| e.to_string())?;
+        let registry = oid_registry();
+        let sans = match cert.subject_alternative_name() {
+            Ok(Some(extension)) => extension
+                .value
+                .general_names
+                .iter()
+                .map(general_name)
+                .collect(),
+            _ => vec![],
+        };
+        let not_before = DateTime::from_timestamp(cert.validity().not_before.timestamp(), 0);
+        let not_after = DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0);
+        let days_until_expiry = not_after.map(|expiry| (expiry - Utc::now()).num_days());
+        let key = cert.public_key();
+        let key_oid = key.algorithm.algorithm.to_id_string();
+        let (key_type, key_size) = match key.parsed() {
+            Ok(PublicKey::RSA(rsa)) => ("RSA".to_string(), rsa.key_size()),
+            Ok(PublicKey::EC(ec)) => ("EC".to_string(), ec.key_size()),
+            Ok(PublicKey::DSA(y)) => ("DSA".to_string(), y.len() * 8),
+            _ if key_oid == OID_ED25519 => ("Ed25519".to_string(), 256),
+            _ if key_oid == OID_ED448 => ("Ed448".to_string(), 456),
+            _ => (
+                oid2sn(&key.algorithm.algorithm, registry)
+                    .map(str::to_string)
+                    .unwrap_or(key_oid),
+                0,
+            ),
+        };
+        let signature_oid = &cert.signature_algorithm.algorithm;
+        Ok(CertificateInfo {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            sans,
+            serial: cert.raw_serial_as_string(),
+            not_before: not_before.map(|t| t.fixed_offset()),
+            not_after: not_after.map(|t| t.fixed_offset()),
+            days_until_expiry,
+            key_type,
+            key_size: key_size as i64,
+            signature_algorithm: oid2sn(signature_oid, registry)
+                .map(str::to_string)
+                .unwrap_or_else(|_| signature_oid.to_id_string()),
+            self_signed: cert.subject() == cert.issuer(),
+        })
+    }
+}
+
+fn general_name(name: &GeneralName) -> String {
+    match name {
+        GeneralName::DNSName(dns) => dns.to_string(),
+        GeneralName::RFC822Name(email) => email.to_string(),
+        GeneralName::URI(uri) => uri.to_string(),
+        GeneralName::IPAddress(bytes) => match bytes.len() {
+            4 => std::net::Ipv4Addr::from(<[u8; 4]>::try_from(*bytes).unwrap()).to_string(),
+            16 => std::net::Ipv6Addr::from(<[u8; 16]>::try_from(*bytes).unwrap()).to_string(),
+            _ => format!("{:02x?}", bytes),
+        },
+        other => other.to_string(),
+    }
+}
+
+impl AsValue for CertificateInfo {
+    fn as_value(self, span: Span) -> nu_protocol::Value {
+        let result: Record = record! {
+            "subject" => self.subject.as_value(span),
+            "issuer" => self.issuer.as_value(span),
+            "sans" => self.sans.as_value(span),
+            "serial" => self.serial.as_value(span),
+            "not_before" => self.not_before.as_value(span),
+            "not_after" => self.not_after.as_value(span),
+            "days_until_expiry" => self.days_until_expiry.as_value(span),
+            "key_type" => self.key_type.as_value(span),
+            "key_size" => self.key_size.as_value(span),
+            "signature_algorithm" => self.signature_algorithm.as_value(span),
+            "self_signed" => self.self_signed.as_value(span),
+        };
+        result.as_value(span)
+    }
+}
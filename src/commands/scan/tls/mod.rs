@@ -0,0 +1,226 @@
+mod certificate;
+mod starttls;
+pub(crate) use starttls::StartTls;
+
+use std::{
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+use nu_protocol::{Span, Value, record};
+use rustls::{
+    ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    StreamOwned,
+    client::{
+        WebPkiServerVerifier,
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    },
+    crypto::ring,
+    pki_types::{CertificateDer, ServerName, UnixTime},
+};
+
+use crate::helpers::AsValue;
+
+use super::interrupt::SignalStream;
+
+use certificate::CertificateInfo;
+
+pub(super) type TlsStream = StreamOwned<ClientConnection, SignalStream>;
+
+/// Outcome of a TLS handshake, a failed handshake is reported here rather than as a closed port
+#[derive(Default, Clone, Debug)]
+pub(super) struct TlsReport {
+    established: bool,
+    error: Option<String>,
+    sni: Option<String>,
+    version: Option<String>,
+    cipher_suite: Option<String>,
+    alpn: Option<String>,
+    trusted: bool,
+    trust_error: Option<String>,
+    certificates: Vec<CertificateInfo>,
+}
+
+impl TlsReport {
+    fn failed(sni: Option<String>, error: String) -> Self {
+        Self {
+            sni,
+            error: Some(error),
+            ..Default::default()
+        }
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+impl AsValue for TlsReport {
+    fn as_value(self, span: Span) -> Value {
+        record! {
+            "established" => self.established.as_value(span),
+            "error" => self.error.as_value(span),
+            "sni" => self.sni.as_value(span),
+            "version" => self.version.as_value(span),
+            "cipher_suite" => self.cipher_suite.as_value(span),
+            "alpn" => self.alpn.as_value(span),
+            "trusted" => self.trusted.as_value(span),
+            "trust_error" => self.trust_error.as_value(span),
+            "certificates" => self.certificates.as_value(span),
+        }
+        .as_value(span)
+    }
+}
+
+/// Accepts every certificate so the chain can be inspected, but remembers
+/// whether it would have passed the regular WebPKI verification
+#[derive(Debug)]
+struct RecordingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    outcome: Mutex<Option<Result<(), rustls::Error>>>,
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let outcome = self
+            .inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+            .map(|_| ());
+        *self.outcome.lock().unwrap() = Some(outcome);
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Upgrades `stream` to TLS (after the optional STARTTLS exchange) and reports the negotiated
+/// parameters. The TLS stream is only returned when the handshake succeeded.
+pub(super) fn handshake(
+    mut stream: SignalStream,
+    target: IpAddr,
+    sni: Option<&str>,
+    starttls: Option<StartTls>,
+    alpn: &[&str],
+) -> (TlsReport, Option<TlsStream>) {
+    let sni_name = sni.map(str::to_string);
+    if let Some(protocol) = starttls
+        && let Err(err) = starttls::upgrade(&mut stream, protocol)
+    {
+        return (TlsReport::failed(sni_name, err), None);
+    }
+    let server_name = match sni {
+        Some(name) => match ServerName::try_from(name.to_string()) {
+            Ok(name) => name,
+            Err(err) => return (TlsReport::failed(sni_name, err.to_string()), None),
+        },
+        None => ServerName::IpAddress(target.into()),
+    };
+    let verifier = match build_verifier() {
+        Ok(verifier) => verifier,
+        Err(err) => return (TlsReport::failed(sni_name, err.to_string()), None),
+    };
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map(|builder| {
+            let mut config = builder
+                .dangerous()
+                .with_custom_certificate_verifier(verifier.clone())
+                .with_no_client_auth();
+            config.alpn_protocols = alpn.iter().map(|p| p.as_bytes().to_vec()).collect();
+            config
+        });
+    let config = match config {
+        Ok(config) => config,
+        Err(err) => return (TlsReport::failed(sni_name, err.to_string()), None),
+    };
+    let mut connection = match ClientConnection::new(Arc::new(config), server_name) {
+        Ok(connection) => connection,
+        Err(err) => return (TlsReport::failed(sni_name, err.to_string()), None),
+    };
+
+    let mut error = None;
+    while connection.is_handshaking() {
+        if let Err(err) = connection.complete_io(&mut stream) {
+            error = Some(err.to_string());
+            break;
+        }
+    }
+
+    let certificates = connection
+        .peer_certificates()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|der| CertificateInfo::parse(der).ok())
+        .collect();
+    let trust = verifier.outcome.lock().unwrap().take();
+    let report = TlsReport {
+        established: error.is_none(),
+        error,
+        sni: sni_name,
+        version: connection.protocol_version().map(version_name),
+        cipher_suite: connection
+            .negotiated_cipher_suite()
+            .map(|suite| format!("{:?}", suite.suite())),
+        alpn: connection
+            .alpn_protocol()
+            .map(|p| String::from_utf8_lossy(p).to_string()),
+        trusted: matches!(trust, Some(Ok(()))),
+        trust_error: match trust {
+            Some(Err(err)) => Some(err.to_string()),
+            _ => None,
+        },
+        certificates,
+    };
+    match report.established {
+        true => (report, Some(StreamOwned::new(connection, stream))),
+        false => (report, None),
+    }
+}
+
+fn build_verifier() -> Result<Arc<RecordingVerifier>, rustls::client::VerifierBuilderError> {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let inner =
+        WebPkiServerVerifier::builder_with_provider(roots.into(), ring::default_provider().into())
+            .build()?;
+    Ok(Arc::new(RecordingVerifier {
+        inner,
+        outcome: Mutex::new(None),
+    }))
+}
+
+fn version_name(version: rustls::ProtocolVersion) -> String {
+    match version {
+        rustls::ProtocolVersion::TLSv1_2 => "TLSv1.2".into(),
+        rustls::ProtocolVersion::TLSv1_3 => "TLSv1.3".into(),
+        other => format!("{:?}", other),
+    }
+}
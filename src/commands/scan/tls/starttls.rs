@@ -0,0 +1,130 @@
+use std::{
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use super::SignalStream;
+
+const MAX_REPLY_SIZE: usize = 16 * 1024;
+/// `SSLRequest` message: length 8 followed by the magic code 80877103
+const POSTGRES_SSL_REQUEST: &[u8] = &[0x00, 0x00, 0x00, 0x08, 0x04, 0xd2, 0x16, 0x2f];
+
+/// Plain text protocols that can be upgraded to TLS in-band
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum StartTls {
+    Smtp,
+    Imap,
+    Pop3,
+    Ftp,
+    Postgres,
+}
+
+impl FromStr for StartTls {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "smtp" => Ok(StartTls::Smtp),
+            "imap" => Ok(StartTls::Imap),
+            "pop3" => Ok(StartTls::Pop3),
+            "ftp" => Ok(StartTls::Ftp),
+            "postgres" | "postgresql" => Ok(StartTls::Postgres),
+            other => Err(format!(
+                "unknown starttls protocol `{}`, expected one of: smtp, imap, pop3, ftp, postgres",
+                other
+            )),
+        }
+    }
+}
+
+/// Runs the plain text part of the protocol until the server is ready for the TLS handshake
+pub(super) fn upgrade(stream: &mut SignalStream, protocol: StartTls) -> Result<(), String> {
+    match protocol {
+        StartTls::Smtp => {
+            expect_code(read_reply(stream, is_final_coded_line)?, "220")?;
+            send(stream, b"EHLO nu-port-scan\r\n")?;
+            expect_code(read_reply(stream, is_final_coded_line)?, "250")?;
+            send(stream, b"STARTTLS\r\n")?;
+            expect_code(read_reply(stream, is_final_coded_line)?, "220")
+        }
+        StartTls::Ftp => {
+            expect_code(read_reply(stream, is_final_coded_line)?, "220")?;
+            send(stream, b"AUTH TLS\r\n")?;
+            expect_code(read_reply(stream, is_final_coded_line)?, "234")
+        }
+        StartTls::Imap => {
+            expect_code(read_reply(stream, |line| line.starts_with("* "))?, "* OK")?;
+            send(stream, b"a001 STARTTLS\r\n")?;
+            expect_code(
+                read_reply(stream, |line| line.starts_with("a001 "))?,
+                "a001 OK",
+            )
+        }
+        StartTls::Pop3 => {
+            expect_code(read_reply(stream, |_| true)?, "+OK")?;
+            send(stream, b"STLS\r\n")?;
+            expect_code(read_reply(stream, |_| true)?, "+OK")
+        }
+        StartTls::Postgres => {
+            send(stream, POSTGRES_SSL_REQUEST)?;
+            let mut answer = [0; 1];
+            stream
+                .read_exact(&mut answer)
+                .map_err(|e| format!("no answer to SSLRequest: {}", e))?;
+            match answer[0] {
+                b'S' => Ok(()),
+                b'N' => Err("server does not accept ssl connections".into()),
+                other => Err(format!("unexpected answer to SSLRequest: 0x{:02x}", other)),
+            }
+        }
+    }
+}
+
+fn send(stream: &mut SignalStream, data: &[u8]) -> Result<(), String> {
+    stream
+        .write_all(data)
+        .map_err(|e| format!("cannot send starttls command: {}", e))
+}
+
+/// Reads lines until one of them satisfies `is_final`, returns that line
+fn read_reply(
+    stream: &mut SignalStream,
+    is_final: impl Fn(&str) -> bool,
+) -> Result<String, String> {
+    let mut buffer = vec![];
+    let mut chunk = [0; 1024];
+    loop {
+        // the last element is either empty or an incomplete line
+        let text = String::from_utf8_lossy(&buffer).to_string();
+        let complete = text.split("\r\n").collect::<Vec<_>>();
+        if let Some(line) = complete[..complete.len() - 1]
+            .iter()
+            .find(|line| is_final(line))
+        {
+            return Ok(line.to_string());
+        }
+        if buffer.len() > MAX_REPLY_SIZE {
+            return Err("starttls reply is too long".into());
+        }
+        match stream.read(&mut chunk) {
+            Ok(0) => return Err("connection closed during starttls negotiation".into()),
+            Ok(size) => buffer.extend_from_slice(&chunk[..size]),
+            Err(e) => return Err(format!("cannot read starttls reply: {}", e)),
+        }
+    }
+}
+
+/// Last line of an SMTP/FTP reply: three digit code followed by a space
+fn is_final_coded_line(line: &str) -> bool {
+    line.len() >= 4 && line.as_bytes()[3] == b' '
+}
+
+fn expect_code(line: String, expected: &str) -> Result<(), String> {
+    match line.starts_with(expected) {
+        true => Ok(()),
+        false => Err(format!(
+            "server refused starttls, expected `{}` but received `{}`",
+            expected, line
+        )),
+    }
+}
@@ -0,0 +1,260 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use nu_protocol::{Record, Span, Value, record};
+
+use crate::helpers::AsValue;
+
+use super::ByteReader;
+
+const QUERY_ID: u16 = 0x1337;
+const MAX_POINTER_JUMPS: usize = 16;
+
+/// Recursive query for the root name servers (`. IN NS`)
+pub(super) fn request() -> Vec<u8> {
+    let mut query = vec![];
+    query.extend_from_slice(&QUERY_ID.to_be_bytes());
+    // flags: recursion desired
+    query.extend_from_slice(&[0x01, 0x00]);
+    // one question, no answer/authority/additional records
+    query.extend_from_slice(&[0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    // root name, type NS, class IN
+    query.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x01]);
+    query
+}
+
+pub(super) fn parse(response: &[u8]) -> Result<Record, String> {
+    let span = Span::unknown();
+    let mut reader = ByteReader::new(response);
+    let id = reader.u16()?;
+    let flags = reader.u16()?;
+    if flags & 0x8000 == 0 {
+        return Err("reply is not a dns response".into());
+    }
+    let question_count = reader.u16()?;
+    let answer_count = reader.u16()?;
+    let authority_count = reader.u16()?;
+    let additional_count = reader.u16()?;
+    for _ in 0..question_count {
+        read_name(&mut reader)?;
+        reader.take(4)?;
+    }
+    let answers = (0..answer_count)
+        .map(|_| read_record(&mut reader))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(record! {
+        "id" => id.as_value(span),
+        "rcode" => rcode_name(flags & 0x000f).as_value(span),
+        "authoritative" => (flags & 0x0400 != 0).as_value(span),
+        "truncated" => (flags & 0x0200 != 0).as_value(span),
+        "recursion_available" => (flags & 0x0080 != 0).as_value(span),
+        "answer_count" => answer_count.as_value(span),
+        "authority_count" => authority_count.as_value(span),
+        "additional_count" => additional_count.as_value(span),
+        "answers" => answers.as_value(span),
+    })
+}
+
+fn read_record(reader: &mut ByteReader) -> Result<Record, String> {
+    let span = Span::unknown();
+    let name = read_name(reader)?;
+    let record_type = reader.u16()?;
+    let _class = reader.u16()?;
+    let ttl = reader.u32()?;
+    let length = reader.u16()? as usize;
+    let data_start = reader.position;
+    let data = reader.take(length)?;
+    let value = match (record_type, length) {
+        (1, 4) => Ipv4Addr::new(data[0], data[1], data[2], data[3])
+            .to_string()
+            .as_value(span),
+        (28, 16) => {
+            let octets: [u8; 16] = data.try_into().map_err(|_| "invalid AAAA record")?;
+            Ipv6Addr::from(octets).to_string().as_value(span)
+        }
+        // NS, CNAME, PTR hold a single (possibly compressed) name
+        (2 | 5 | 12, _) => {
+            let mut name_reader = ByteReader {
+                data: reader.data,
+                position: data_start,
+            };
+            read_name(&mut name_reader)?.as_value(span)
+        }
+        (15, _) => {
+            let mut mx_reader = ByteReader {
+                data: reader.data,
+                position: data_start,
+            };
+            let preference = mx_reader.u16()?;
+            format!("{} {}", preference, read_name(&mut mx_reader)?).as_value(span)
+        }
+        (16, _) => {
+            let mut txt_reader = ByteReader::new(data);
+            let mut parts = vec![];
+            while txt_reader.remaining() > 0 {
+                let size = txt_reader.u8()? as usize;
+                parts.push(String::from_utf8_lossy(txt_reader.take(size)?).to_string());
+            }
+            parts.join("").as_value(span)
+        }
+        _ => Value::binary(data.to_vec(), span),
+    };
+    Ok(record! {
+        "name" => name.as_value(span),
+        "type" => type_name(record_type).as_value(span),
+        "ttl" => ttl.as_value(span),
+        "data" => value,
+    })
+}
+
+/// Reads a domain name, following compression pointers
+fn read_name(reader: &mut ByteReader) -> Result<String, String> {
+    let mut labels: Vec<String> = vec![];
+    let mut cursor = ByteReader {
+        data: reader.data,
+        position: reader.position,
+    };
+    let mut jumps = 0;
+    loop {
+        let size = cursor.u8()?;
+        match size {
+            0 => break,
+            size if size & 0xc0 == 0xc0 => {
+                let offset = (((size & 0x3f) as usize) << 8) | cursor.u8()? as usize;
+                if jumps == 0 {
+                    reader.position = cursor.position;
+                }
+                jumps += 1;
+                if jumps > MAX_POINTER_JUMPS {
+                    return Err("too many compression pointers in name".into());
+                }
+                cursor.position = offset;
+            }
+            size => {
+                let label = cursor.take(size as usize)?;
+                labels.push(String::from_utf8_lossy(label).to_string());
+            }
+        }
+    }
+    if jumps == 0 {
+        reader.position = cursor.position;
+    }
+    Ok(format!("{}.", labels.join(".")))
+}
+
+fn rcode_name(rcode: u16) -> String {
+    match rcode {
+        0 => "NOERROR".into(),
+        1 => "FORMERR".into(),
+        2 => "SERVFAIL".into(),
+        3 => "NXDOMAIN".into(),
+        4 => "NOTIMP".into(),
+        5 => "REFUSED".into(),
+        other => other.to_string(),
+    }
+}
+
+fn type_name(record_type: u16) -> String {
+    match record_type {
+        1 => "A".into(),
+        2 => "NS".into(),
+        5 => "CNAME".into(),
+        6 => "SOA".into(),
+        12 => "PTR".into(),
+        15 => "MX".into(),
+        16 => "TXT".into(),
+        28 => "AAAA".into(),
+        other => format!("TYPE{}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::Value;
+
+    use super::super::{UdpProbe, tests::exchange};
+    use super::*;
+
+    /// Reply to the root NS query: one NS answer, then an A record whose name
+    /// points at the NS data (offset 28)
+    fn reply() -> Vec<u8> {
+        let mut reply = vec![0x13, 0x37, 0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 0];
+        reply.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x01]);
+        reply.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x07, 0xe9, 0x00, 0, 20]);
+        reply.extend_from_slice(b"\x01a\x0croot-servers\x03net\x00");
+        reply.extend_from_slice(&[0xc0, 28, 0x00, 0x01, 0x00, 0x01, 0, 0, 0x0e, 0x10, 0, 4]);
+        reply.extend_from_slice(&[198, 41, 0, 4]);
+        reply
+    }
+
+    #[test]
+    fn request_asks_for_root_name_servers() {
+        let request = request();
+        assert_eq!(request.len(), 17);
+        assert_eq!(&request[..2], &QUERY_ID.to_be_bytes());
+        // one question: the root name, type NS, class IN
+        assert_eq!(&request[4..6], &[0, 1]);
+        assert_eq!(&request[12..], &[0, 0, 2, 0, 1]);
+    }
+
+    #[test]
+    fn parses_answers_from_stand_in() {
+        let parsed = exchange(UdpProbe::Dns, &reply()).unwrap();
+        assert_eq!(parsed.get("id"), Some(&Value::test_int(0x1337)));
+        assert_eq!(parsed.get("rcode"), Some(&Value::test_string("NOERROR")));
+        assert_eq!(
+            parsed.get("recursion_available"),
+            Some(&Value::test_bool(true))
+        );
+        let answers = parsed.get("answers").unwrap().as_list().unwrap();
+        let ns = answers[0].as_record().unwrap();
+        assert_eq!(ns.get("name"), Some(&Value::test_string(".")));
+        assert_eq!(ns.get("type"), Some(&Value::test_string("NS")));
+        assert_eq!(ns.get("ttl"), Some(&Value::test_int(518_400)));
+        assert_eq!(
+            ns.get("data"),
+            Some(&Value::test_string("a.root-servers.net."))
+        );
+        let a = answers[1].as_record().unwrap();
+        assert_eq!(
+            a.get("name"),
+            Some(&Value::test_string("a.root-servers.net."))
+        );
+        assert_eq!(a.get("data"), Some(&Value::test_string("198.41.0.4")));
+    }
+
+    #[test]
+    fn reports_error_rcode() {
+        let reply = [0x13, 0x37, 0x81, 0x83, 0, 0, 0, 0, 0, 0, 0, 0];
+        let parsed = parse(&reply).unwrap();
+        assert_eq!(parsed.get("rcode"), Some(&Value::test_string("NXDOMAIN")));
+    }
+
+    #[test]
+    fn rejects_truncated_reply() {
+        let reply = reply();
+        for length in [0, 5, 16, 30, reply.len() - 1] {
+            assert!(parse(&reply[..length]).is_err(), "length {}", length);
+        }
+    }
+
+    #[test]
+    fn rejects_queries() {
+        assert_eq!(
+            parse(&request()),
+            Err("reply is not a dns response".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_compression_loops() {
+        // the question name points at itself
+        let reply = [
+            0x13, 0x37, 0x81, 0x80, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 12, 0, 2, 0, 1,
+        ];
+        assert_eq!(
+            parse(&reply),
+            Err("too many compression pointers in name".to_string())
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use nu_protocol::{Record, Span, record};
+
+use crate::helpers::AsValue;
+
+use super::ByteReader;
+
+const REQUEST_ID: u16 = 0x1337;
+
+/// `version` command wrapped in the memcached udp frame header
+pub(super) fn request() -> Vec<u8> {
+    let mut packet = vec![];
+    packet.extend_from_slice(&REQUEST_ID.to_be_bytes());
+    // sequence number 0, 1 datagram in total, reserved
+    packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x00, 0x00]);
+    packet.extend_from_slice(b"version\r\n");
+    packet
+}
+
+pub(super) fn parse(response: &[u8]) -> Result<Record, String> {
+    let span = Span::unknown();
+    let mut reader = ByteReader::new(response);
+    let _frame_header = reader.take(8)?;
+    let body = String::from_utf8_lossy(reader.take(reader.remaining())?).to_string();
+    let version = body
+        .trim_end()
+        .strip_prefix("VERSION ")
+        .ok_or("reply is not a memcached version response")?;
+
+    Ok(record! {
+        "version" => version.as_value(span),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::Value;
+
+    use super::super::{UdpProbe, tests::exchange};
+    use super::*;
+
+    fn reply(body: &[u8]) -> Vec<u8> {
+        [&[0x13, 0x37, 0, 0, 0, 1, 0, 0][..], body].concat()
+    }
+
+    #[test]
+    fn request_is_a_framed_version_command() {
+        let request = request();
+        assert_eq!(&request[..2], &REQUEST_ID.to_be_bytes());
+        assert_eq!(&request[2..8], &[0, 0, 0, 1, 0, 0]);
+        assert_eq!(&request[8..], b"version\r\n");
+    }
+
+    #[test]
+    fn parses_version_from_stand_in() {
+        let parsed = exchange(UdpProbe::Memcached, &reply(b"VERSION 1.6.21\r\n")).unwrap();
+        assert_eq!(parsed.get("version"), Some(&Value::test_string("1.6.21")));
+    }
+
+    #[test]
+    fn rejects_truncated_reply() {
+        assert_eq!(
+            parse(&reply(b"")[..5]),
+            Err("reply is truncated".to_string())
+        );
+        assert_eq!(
+            parse(&reply(b"VERS")),
+            Err("reply is not a memcached version response".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_other_replies() {
+        assert_eq!(
+            parse(&reply(b"ERROR\r\n")),
+            Err("reply is not a memcached version response".to_string())
+        );
+    }
+}
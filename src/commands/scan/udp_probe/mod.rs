@@ -0,0 +1,185 @@
+mod dns;
+mod memcached;
+mod ntp;
+mod snmp;
+mod ssdp;
+mod stun;
+
+use std::str::FromStr;
+
+use nu_protocol::Record;
+
+/// Request generators and reply parsers for common udp services
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum UdpProbe {
+    Dns,
+    Ntp,
+    Snmp,
+    Ssdp,
+    Stun,
+    Memcached,
+}
+
+impl UdpProbe {
+    pub const NAMES: &[&str] = &["dns", "ntp", "snmp", "ssdp", "stun", "memcached"];
+
+    /// Probe matching the well-known port of a service
+    pub fn for_port(port: u16) -> Option<Self> {
+        match port {
+            53 => Some(UdpProbe::Dns),
+            123 => Some(UdpProbe::Ntp),
+            161 => Some(UdpProbe::Snmp),
+            1900 => Some(UdpProbe::Ssdp),
+            3478 => Some(UdpProbe::Stun),
+            11211 => Some(UdpProbe::Memcached),
+            _ => None,
+        }
+    }
+
+    pub fn request(&self) -> Vec<u8> {
+        match self {
+            UdpProbe::Dns => dns::request(),
+            UdpProbe::Ntp => ntp::request(),
+            UdpProbe::Snmp => snmp::request(),
+            UdpProbe::Ssdp => ssdp::request(),
+            UdpProbe::Stun => stun::request(),
+            UdpProbe::Memcached => memcached::request(),
+        }
+    }
+
+    pub fn parse(&self, response: &[u8]) -> Result<Record, String> {
+        match self {
+            UdpProbe::Dns => dns::parse(response),
+            UdpProbe::Ntp => ntp::parse(response),
+            UdpProbe::Snmp => snmp::parse(response),
+            UdpProbe::Ssdp => ssdp::parse(response),
+            UdpProbe::Stun => stun::parse(response),
+            UdpProbe::Memcached => memcached::parse(response),
+        }
+    }
+}
+
+impl FromStr for UdpProbe {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dns" => Ok(UdpProbe::Dns),
+            "ntp" => Ok(UdpProbe::Ntp),
+            "snmp" => Ok(UdpProbe::Snmp),
+            "ssdp" => Ok(UdpProbe::Ssdp),
+            "stun" => Ok(UdpProbe::Stun),
+            "memcached" => Ok(UdpProbe::Memcached),
+            other => Err(format!(
+                "unknown probe `{}`, expected one of: {}",
+                other,
+                UdpProbe::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Payload sent to a udp port when the user did not provide any `send` data.
+/// Well-known services get a request they will answer, anything else gets an empty datagram
+/// which is still enough to trigger an ICMP port-unreachable from closed ports.
+pub(super) fn default_payload(port: u16) -> Vec<u8> {
+    UdpProbe::for_port(port)
+        .map(|probe| probe.request())
+        .unwrap_or_default()
+}
+
+/// Big-endian cursor over a reply, every read fails once the reply is exhausted
+struct ByteReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|end| *end <= self.data.len())
+            .ok_or("reply is truncated")?;
+        let slice = &self.data[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::UdpSocket, time::Duration};
+
+    use super::*;
+
+    /// Sends the request of `probe` to a local stand-in answering with `reply`,
+    /// then parses what came back
+    pub(super) fn exchange(probe: UdpProbe, reply: &[u8]) -> Result<Record, String> {
+        let responder = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        responder
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client.connect(responder.local_addr().unwrap()).unwrap();
+        client.send(&probe.request()).unwrap();
+
+        let mut buffer = [0; 2048];
+        let (size, peer) = responder.recv_from(&mut buffer).unwrap();
+        assert_eq!(&buffer[..size], probe.request().as_slice());
+        responder.send_to(reply, peer).unwrap();
+        let size = client.recv(&mut buffer).unwrap();
+        probe.parse(&buffer[..size])
+    }
+
+    #[test]
+    fn names_parse_to_probes() {
+        for name in UdpProbe::NAMES {
+            let probe = UdpProbe::from_str(name).unwrap();
+            assert_eq!(UdpProbe::from_str(&name.to_uppercase()), Ok(probe));
+        }
+        assert!(UdpProbe::from_str("quic").is_err());
+    }
+
+    #[test]
+    fn well_known_ports_get_a_request() {
+        assert_eq!(default_payload(53), UdpProbe::Dns.request());
+        assert_eq!(default_payload(11211), UdpProbe::Memcached.request());
+        assert!(default_payload(9999).is_empty());
+    }
+
+    #[test]
+    fn byte_reader_fails_past_the_end() {
+        let mut reader = ByteReader::new(&[0x12, 0x34, 0x56]);
+        assert_eq!(reader.u16(), Ok(0x1234));
+        assert_eq!(reader.remaining(), 1);
+        assert!(reader.u16().is_err());
+        assert!(reader.take(usize::MAX).is_err());
+        assert_eq!(reader.u8(), Ok(0x56));
+    }
+}
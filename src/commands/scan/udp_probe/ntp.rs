@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use chrono::DateTime;
+use nu_protocol::{Record, Span, record};
+
+use crate::helpers::AsValue;
+
+use super::ByteReader;
+
+/// Seconds between the NTP era (1900-01-01) and the unix epoch
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// NTPv3 client request (48 bytes, `LI = 0`, `VN = 3`, `Mode = 3`)
+pub(super) fn request() -> Vec<u8> {
+    let mut packet = vec![0; 48];
+    packet[0] = 0x1b;
+    packet
+}
+
+pub(super) fn parse(response: &[u8]) -> Result<Record, String> {
+    let span = Span::unknown();
+    let mut reader = ByteReader::new(response);
+    let header = reader.u8()?;
+    let mode = header & 0x07;
+    if mode != 4 && mode != 5 {
+        return Err(format!(
+            "reply is not an ntp server message (mode {})",
+            mode
+        ));
+    }
+    let stratum = reader.u8()?;
+    let poll = log2_seconds(reader.u8()? as i8, "poll")?;
+    let precision = log2_seconds(reader.u8()? as i8, "precision")?;
+    let root_delay = short_format(reader.u32()?);
+    let root_dispersion = short_format(reader.u32()?);
+    let refid = reader.take(4)?;
+    let refid = match stratum {
+        // primary servers and kiss-o'-death packets carry an ascii code
+        0 | 1 => String::from_utf8_lossy(refid)
+            .trim_end_matches('\0')
+            .to_string(),
+        _ => format!("{}.{}.{}.{}", refid[0], refid[1], refid[2], refid[3]),
+    };
+    let reference_time = timestamp(&mut reader)?;
+    let _origin_time = timestamp(&mut reader)?;
+    let _receive_time = timestamp(&mut reader)?;
+    let transmit_time = timestamp(&mut reader)?;
+
+    Ok(record! {
+        "leap_indicator" => u16::from(header >> 6).as_value(span),
+        "version" => u16::from((header >> 3) & 0x07).as_value(span),
+        "mode" => u16::from(mode).as_value(span),
+        "stratum" => u16::from(stratum).as_value(span),
+        "poll" => poll.as_value(span),
+        "precision" => precision.as_value(span),
+        "root_delay" => root_delay.as_value(span),
+        "root_dispersion" => root_dispersion.as_value(span),
+        "refid" => refid.as_value(span),
+        "reference_time" => reference_time.as_value(span),
+        "transmit_time" => transmit_time.as_value(span),
+    })
+}
+
+/// Interval encoded as a power of two seconds, rejected when it does not fit a duration
+fn log2_seconds(exponent: i8, field: &str) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(2f64.powi(exponent.into()))
+        .map_err(|_| format!("{} of 2^{} seconds is out of range", field, exponent))
+}
+
+/// 16.16 fixed point seconds
+fn short_format(value: u32) -> Duration {
+    Duration::from_secs_f64(value as f64 / 65536.0)
+}
+
+/// 32.32 fixed point seconds since 1900, `None` when unset
+fn timestamp(
+    reader: &mut ByteReader,
+) -> Result<Option<chrono::DateTime<chrono::FixedOffset>>, String> {
+    let seconds = reader.u32()? as i64;
+    let fraction = reader.u32()? as u64;
+    if seconds == 0 && fraction == 0 {
+        return Ok(None);
+    }
+    let nanos = ((fraction * 1_000_000_000) >> 32) as u32;
+    Ok(DateTime::from_timestamp(seconds - NTP_UNIX_OFFSET, nanos).map(|t| t.fixed_offset()))
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::Value;
+
+    use super::super::{UdpProbe, tests::exchange};
+    use super::*;
+
+    /// Stratum 2 server reply, version 4, poll 2^6 s, precision 2^-20 s
+    fn reply() -> Vec<u8> {
+        let mut reply = vec![0x24, 2, 6, 0xec];
+        reply.extend_from_slice(&0x0001_8000u32.to_be_bytes());
+        reply.extend_from_slice(&0x0000_4000u32.to_be_bytes());
+        reply.extend_from_slice(&[192, 0, 2, 1]);
+        let seconds = (NTP_UNIX_OFFSET + 1_700_000_000) as u32;
+        reply.extend_from_slice(&seconds.to_be_bytes());
+        reply.extend_from_slice(&[0; 4]);
+        // origin and receive timestamps
+        reply.extend_from_slice(&[0; 16]);
+        reply.extend_from_slice(&(seconds + 10).to_be_bytes());
+        reply.extend_from_slice(&0x8000_0000u32.to_be_bytes());
+        reply
+    }
+
+    #[test]
+    fn request_is_a_client_packet() {
+        let request = request();
+        assert_eq!(request.len(), 48);
+        // version 3, mode 3 (client)
+        assert_eq!(request[0], 0x1b);
+        assert!(request[1..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn parses_server_reply_from_stand_in() {
+        let parsed = exchange(UdpProbe::Ntp, &reply()).unwrap();
+        assert_eq!(parsed.get("version"), Some(&Value::test_int(4)));
+        assert_eq!(parsed.get("mode"), Some(&Value::test_int(4)));
+        assert_eq!(parsed.get("stratum"), Some(&Value::test_int(2)));
+        assert_eq!(parsed.get("refid"), Some(&Value::test_string("192.0.2.1")));
+        assert_eq!(
+            parsed.get("poll"),
+            Some(&Value::test_duration(64_000_000_000))
+        );
+        assert_eq!(
+            parsed.get("root_delay"),
+            Some(&Value::test_duration(1_500_000_000))
+        );
+        let reference = parsed.get("reference_time").unwrap().as_date().unwrap();
+        assert_eq!(reference.timestamp(), 1_700_000_000);
+        let transmit = parsed.get("transmit_time").unwrap().as_date().unwrap();
+        assert_eq!(transmit.timestamp(), 1_700_000_010);
+        assert_eq!(transmit.timestamp_subsec_millis(), 500);
+    }
+
+    #[test]
+    fn primary_server_refid_is_ascii() {
+        let mut reply = reply();
+        reply[1] = 1;
+        reply[12..16].copy_from_slice(b"GPS\0");
+        let parsed = parse(&reply).unwrap();
+        assert_eq!(parsed.get("refid"), Some(&Value::test_string("GPS")));
+    }
+
+    #[test]
+    fn unset_timestamps_are_nothing() {
+        let mut reply = reply();
+        reply[16..24].fill(0);
+        let parsed = parse(&reply).unwrap();
+        assert_eq!(parsed.get("reference_time"), Some(&Value::test_nothing()));
+    }
+
+    #[test]
+    fn rejects_truncated_reply() {
+        let reply = reply();
+        for length in [0, 1, 12, 47] {
+            assert_eq!(
+                parse(&reply[..length]),
+                Err("reply is truncated".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_client_packets() {
+        assert_eq!(
+            parse(&request()),
+            Err("reply is not an ntp server message (mode 3)".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_intervals() {
+        let mut poll = reply();
+        poll[2] = 127;
+        assert_eq!(
+            parse(&poll),
+            Err("poll of 2^127 seconds is out of range".to_string())
+        );
+        let mut precision = reply();
+        precision[3] = 64;
+        assert_eq!(
+            parse(&precision),
+            Err("precision of 2^64 seconds is out of range".to_string())
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use nu_protocol::{Record, Span, record};
+
+use crate::helpers::AsValue;
+
+use super::ByteReader;
+
+const COMMUNITY: &[u8] = b"public";
+/// `1.3.6.1.2.1.1.1.0` (sysDescr.0)
+const SYS_DESCR_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x01, 0x00];
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_GET_REQUEST: u8 = 0xa0;
+const TAG_GET_RESPONSE: u8 = 0xa2;
+
+/// SNMPv1 GetRequest for sysDescr.0 using the `public` community
+pub(super) fn request() -> Vec<u8> {
+    let varbind = tlv(
+        TAG_SEQUENCE,
+        &[tlv(TAG_OID, SYS_DESCR_OID), tlv(TAG_NULL, &[])].concat(),
+    );
+    let pdu = tlv(
+        TAG_GET_REQUEST,
+        &[
+            tlv(TAG_INTEGER, &[0x01]),
+            tlv(TAG_INTEGER, &[0x00]),
+            tlv(TAG_INTEGER, &[0x00]),
+            tlv(TAG_SEQUENCE, &varbind),
+        ]
+        .concat(),
+    );
+    tlv(
+        TAG_SEQUENCE,
+        &[
+            tlv(TAG_INTEGER, &[0x00]),
+            tlv(TAG_OCTET_STRING, COMMUNITY),
+            pdu,
+        ]
+        .concat(),
+    )
+}
+
+pub(super) fn parse(response: &[u8]) -> Result<Record, String> {
+    let span = Span::unknown();
+    let mut message = ByteReader::new(expect(&mut ByteReader::new(response), TAG_SEQUENCE)?);
+    let version = integer(expect(&mut message, TAG_INTEGER)?);
+    let community = String::from_utf8_lossy(expect(&mut message, TAG_OCTET_STRING)?).to_string();
+    let mut pdu = ByteReader::new(expect(&mut message, TAG_GET_RESPONSE)?);
+    let _request_id = expect(&mut pdu, TAG_INTEGER)?;
+    let error_status = integer(expect(&mut pdu, TAG_INTEGER)?);
+    let _error_index = expect(&mut pdu, TAG_INTEGER)?;
+    let mut varbinds = ByteReader::new(expect(&mut pdu, TAG_SEQUENCE)?);
+    let mut varbind = ByteReader::new(expect(&mut varbinds, TAG_SEQUENCE)?);
+    let oid = oid_string(expect(&mut varbind, TAG_OID)?);
+    let (tag, value) = read_tlv(&mut varbind)?;
+    let sys_descr = match tag {
+        TAG_OCTET_STRING => Some(String::from_utf8_lossy(value).to_string()),
+        _ => None,
+    };
+
+    Ok(record! {
+        "version" => match version {
+            0 => "v1".to_string(),
+            1 => "v2c".to_string(),
+            other => other.to_string(),
+        }.as_value(span),
+        "community" => community.as_value(span),
+        "error_status" => error_name(error_status).as_value(span),
+        "oid" => oid.as_value(span),
+        "sys_descr" => sys_descr.as_value(span),
+    })
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    match content.len() {
+        len if len < 0x80 => out.push(len as u8),
+        len if len <= 0xff => out.extend_from_slice(&[0x81, len as u8]),
+        len => out.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]),
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn read_tlv<'a>(reader: &mut ByteReader<'a>) -> Result<(u8, &'a [u8]), String> {
+    let tag = reader.u8()?;
+    let length = match reader.u8()? {
+        short if short < 0x80 => short as usize,
+        0x81 => reader.u8()? as usize,
+        0x82 => reader.u16()? as usize,
+        other => return Err(format!("unsupported ber length form 0x{:02x}", other)),
+    };
+    Ok((tag, reader.take(length)?))
+}
+
+fn expect<'a>(reader: &mut ByteReader<'a>, expected: u8) -> Result<&'a [u8], String> {
+    match read_tlv(reader)? {
+        (tag, content) if tag == expected => Ok(content),
+        (tag, _) => Err(format!(
+            "unexpected ber tag 0x{:02x} (expected 0x{:02x})",
+            tag, expected
+        )),
+    }
+}
+
+fn integer(content: &[u8]) -> i64 {
+    let initial: i64 = match content.first() {
+        Some(first) if first & 0x80 != 0 => -1,
+        _ => 0,
+    };
+    content
+        .iter()
+        .fold(initial, |acc, byte| (acc << 8) | *byte as i64)
+}
+
+fn oid_string(content: &[u8]) -> String {
+    let mut parts = vec![];
+    if let Some(first) = content.first() {
+        parts.push((first / 40) as u64);
+        parts.push((first % 40) as u64);
+    }
+    let mut value: u64 = 0;
+    for byte in content.iter().skip(1) {
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            parts.push(value);
+            value = 0;
+        }
+    }
+    parts
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn error_name(status: i64) -> String {
+    match status {
+        0 => "noError".into(),
+        1 => "tooBig".into(),
+        2 => "noSuchName".into(),
+        3 => "badValue".into(),
+        4 => "readOnly".into(),
+        5 => "genErr".into(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::Value;
+
+    use super::super::{UdpProbe, tests::exchange};
+    use super::*;
+
+    /// GetResponse carrying `value` for sysDescr.0
+    fn reply(value: &[u8]) -> Vec<u8> {
+        let varbind = tlv(
+            TAG_SEQUENCE,
+            &[tlv(TAG_OID, SYS_DESCR_OID), value.to_vec()].concat(),
+        );
+        let pdu = tlv(
+            TAG_GET_RESPONSE,
+            &[
+                tlv(TAG_INTEGER, &[0x13, 0x37]),
+                tlv(TAG_INTEGER, &[0]),
+                tlv(TAG_INTEGER, &[0]),
+                tlv(TAG_SEQUENCE, &varbind),
+            ]
+            .concat(),
+        );
+        tlv(
+            TAG_SEQUENCE,
+            &[
+                tlv(TAG_INTEGER, &[0]),
+                tlv(TAG_OCTET_STRING, COMMUNITY),
+                pdu,
+            ]
+            .concat(),
+        )
+    }
+
+    #[test]
+    fn request_gets_sys_descr() {
+        let request = request();
+        assert_eq!(request[0], TAG_SEQUENCE);
+        assert_eq!(request[1] as usize, request.len() - 2);
+        assert!(request.windows(COMMUNITY.len()).any(|w| w == COMMUNITY));
+        assert!(
+            request
+                .windows(SYS_DESCR_OID.len())
+                .any(|w| w == SYS_DESCR_OID)
+        );
+        // a request is not a response
+        assert!(parse(&request).is_err());
+    }
+
+    #[test]
+    fn parses_sys_descr_from_stand_in() {
+        let reply = reply(&tlv(TAG_OCTET_STRING, b"Linux router 6.1"));
+        let parsed = exchange(UdpProbe::Snmp, &reply).unwrap();
+        assert_eq!(parsed.get("version"), Some(&Value::test_string("v1")));
+        assert_eq!(parsed.get("community"), Some(&Value::test_string("public")));
+        assert_eq!(
+            parsed.get("error_status"),
+            Some(&Value::test_string("noError"))
+        );
+        assert_eq!(
+            parsed.get("oid"),
+            Some(&Value::test_string("1.3.6.1.2.1.1.1.0"))
+        );
+        assert_eq!(
+            parsed.get("sys_descr"),
+            Some(&Value::test_string("Linux router 6.1"))
+        );
+    }
+
+    #[test]
+    fn parses_long_form_lengths() {
+        let descr = "x".repeat(300);
+        let parsed = parse(&reply(&tlv(TAG_OCTET_STRING, descr.as_bytes()))).unwrap();
+        assert_eq!(parsed.get("sys_descr"), Some(&Value::test_string(descr)));
+    }
+
+    #[test]
+    fn missing_value_is_nothing() {
+        let parsed = parse(&reply(&tlv(TAG_NULL, &[]))).unwrap();
+        assert_eq!(parsed.get("sys_descr"), Some(&Value::test_nothing()));
+    }
+
+    #[test]
+    fn rejects_truncated_reply() {
+        let reply = reply(&tlv(TAG_OCTET_STRING, b"Linux"));
+        for length in [0, 1, 2, 10, reply.len() - 1] {
+            assert!(parse(&reply[..length]).is_err(), "length {}", length);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_reply() {
+        assert!(parse(b"not snmp").is_err());
+        // four byte long form lengths are not supported
+        assert_eq!(
+            parse(&[TAG_SEQUENCE, 0x84, 0, 0, 0, 1, 0]),
+            Err("unsupported ber length form 0x84".to_string())
+        );
+    }
+}
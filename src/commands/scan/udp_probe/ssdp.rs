@@ -0,0 +1,115 @@
+use nu_protocol::{Record, Span, record};
+
+use crate::helpers::AsValue;
+
+/// Unicast `M-SEARCH` asking for every device and service
+pub(super) fn request() -> Vec<u8> {
+    b"M-SEARCH * HTTP/1.1\r\n\
+      HOST: 239.255.255.250:1900\r\n\
+      MAN: \"ssdp:discover\"\r\n\
+      MX: 1\r\n\
+      ST: ssdp:all\r\n\r\n"
+        .to_vec()
+}
+
+pub(super) fn parse(response: &[u8]) -> Result<Record, String> {
+    let span = Span::unknown();
+    let text = String::from_utf8_lossy(response);
+    let mut lines = text.split("\r\n");
+    let status_line = lines.next().unwrap_or_default();
+    let status = status_line
+        .strip_prefix("HTTP/1.1 ")
+        .or_else(|| status_line.strip_prefix("HTTP/1.0 "))
+        .ok_or("reply is not an ssdp response")?;
+    let status_code = status
+        .split_whitespace()
+        .next()
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or("reply has no status code")?;
+    let mut headers = Record::new();
+    for line in lines.take_while(|line| !line.is_empty()) {
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().as_value(span));
+        }
+    }
+    let header = |name: &str| headers.get(name).cloned().as_value(span);
+
+    Ok(record! {
+        "status" => status_code.as_value(span),
+        "server" => header("server"),
+        "location" => header("location"),
+        "st" => header("st"),
+        "usn" => header("usn"),
+        "headers" => headers.clone().as_value(span),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::Value;
+
+    use super::super::{UdpProbe, tests::exchange};
+    use super::*;
+
+    const REPLY: &[u8] = b"HTTP/1.1 200 OK\r\n\
+        CACHE-CONTROL: max-age=1800\r\n\
+        LOCATION: http://192.0.2.1:49152/description.xml\r\n\
+        SERVER: Linux/6.1 UPnP/1.0 test/1.0\r\n\
+        ST: upnp:rootdevice\r\n\
+        USN: uuid:1234::upnp:rootdevice\r\n\r\n";
+
+    #[test]
+    fn request_searches_everything() {
+        let request = String::from_utf8(request()).unwrap();
+        assert!(request.starts_with("M-SEARCH * HTTP/1.1\r\n"));
+        assert!(request.contains("\r\nST: ssdp:all\r\n"));
+        assert!(request.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn parses_response_from_stand_in() {
+        let parsed = exchange(UdpProbe::Ssdp, REPLY).unwrap();
+        assert_eq!(parsed.get("status"), Some(&Value::test_int(200)));
+        assert_eq!(
+            parsed.get("server"),
+            Some(&Value::test_string("Linux/6.1 UPnP/1.0 test/1.0"))
+        );
+        assert_eq!(
+            parsed.get("location"),
+            Some(&Value::test_string(
+                "http://192.0.2.1:49152/description.xml"
+            ))
+        );
+        assert_eq!(
+            parsed.get("st"),
+            Some(&Value::test_string("upnp:rootdevice"))
+        );
+        let headers = parsed.get("headers").unwrap().as_record().unwrap();
+        assert_eq!(
+            headers.get("cache-control"),
+            Some(&Value::test_string("max-age=1800"))
+        );
+    }
+
+    #[test]
+    fn missing_headers_are_nothing() {
+        let parsed = parse(b"HTTP/1.1 200 OK\r\nST: upnp:rootdevice").unwrap();
+        assert_eq!(parsed.get("usn"), Some(&Value::test_nothing()));
+        assert_eq!(
+            parsed.get("st"),
+            Some(&Value::test_string("upnp:rootdevice"))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_reply() {
+        let not_ssdp = Err("reply is not an ssdp response".to_string());
+        assert_eq!(parse(b""), not_ssdp);
+        assert_eq!(parse(&request()), not_ssdp);
+        assert_eq!(parse(&REPLY[..6]), not_ssdp);
+        assert_eq!(
+            parse(b"HTTP/1.1 OK\r\n\r\n"),
+            Err("reply has no status code".to_string())
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use nu_protocol::{Record, Span, record};
+
+use crate::helpers::AsValue;
+
+use super::ByteReader;
+
+const MAGIC_COOKIE: u32 = 0x2112_a442;
+const TRANSACTION_ID: &[u8; 12] = b"nu-port-scan";
+
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const BINDING_ERROR: u16 = 0x0111;
+
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_ERROR_CODE: u16 = 0x0009;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const ATTR_SOFTWARE: u16 = 0x8022;
+
+/// STUN binding request without attributes (RFC 5389)
+pub(super) fn request() -> Vec<u8> {
+    let mut packet = vec![];
+    packet.extend_from_slice(&BINDING_REQUEST.to_be_bytes());
+    packet.extend_from_slice(&0u16.to_be_bytes());
+    packet.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    packet.extend_from_slice(TRANSACTION_ID);
+    packet
+}
+
+pub(super) fn parse(response: &[u8]) -> Result<Record, String> {
+    let span = Span::unknown();
+    let mut reader = ByteReader::new(response);
+    let message_type = reader.u16()?;
+    let length = reader.u16()? as usize;
+    if reader.u32()? != MAGIC_COOKIE {
+        return Err("reply is not a stun message".into());
+    }
+    let transaction_id = reader.take(12)?;
+    let mut attributes = ByteReader::new(reader.take(length)?);
+
+    let mut mapped = None;
+    let mut software = None;
+    let mut error = None;
+    while attributes.remaining() >= 4 {
+        let attribute = attributes.u16()?;
+        let size = attributes.u16()? as usize;
+        let value = attributes.take(size)?;
+        // attributes are padded to a multiple of 4 bytes
+        attributes.take((4 - size % 4) % 4).ok();
+        match attribute {
+            ATTR_XOR_MAPPED_ADDRESS => mapped = Some(read_address(value, Some(transaction_id))?),
+            ATTR_MAPPED_ADDRESS if mapped.is_none() => mapped = Some(read_address(value, None)?),
+            ATTR_SOFTWARE => software = Some(String::from_utf8_lossy(value).to_string()),
+            ATTR_ERROR_CODE if value.len() >= 4 => {
+                let code = (value[2] & 0x07) as u16 * 100 + value[3] as u16;
+                let reason = String::from_utf8_lossy(&value[4..]);
+                error = Some(format!("{} {}", code, reason).trim().to_string());
+            }
+            _ => {}
+        }
+    }
+
+    Ok(record! {
+        "message_type" => match message_type {
+            BINDING_SUCCESS => "binding_success".to_string(),
+            BINDING_ERROR => "binding_error".to_string(),
+            other => format!("0x{:04x}", other),
+        }.as_value(span),
+        "mapped_address" => mapped.map(|(ip, _)| ip.to_string()).as_value(span),
+        "mapped_port" => mapped.map(|(_, port)| port).as_value(span),
+        "software" => software.as_value(span),
+        "error" => error.as_value(span),
+    })
+}
+
+/// Decodes a (XOR-)MAPPED-ADDRESS value, `transaction_id` is only given for the xor variant
+fn read_address(value: &[u8], transaction_id: Option<&[u8]>) -> Result<(IpAddr, u16), String> {
+    let mut reader = ByteReader::new(value);
+    let _reserved = reader.u8()?;
+    let family = reader.u8()?;
+    let mut port = reader.u16()?;
+    let mut mask = MAGIC_COOKIE.to_be_bytes().to_vec();
+    if let Some(id) = transaction_id {
+        mask.extend_from_slice(id);
+        port ^= (MAGIC_COOKIE >> 16) as u16;
+    }
+    let xor = |bytes: &[u8]| -> Vec<u8> {
+        match transaction_id {
+            Some(_) => bytes.iter().zip(&mask).map(|(b, m)| b ^ m).collect(),
+            None => bytes.to_vec(),
+        }
+    };
+    let ip = match family {
+        0x01 => {
+            let octets: [u8; 4] = xor(reader.take(4)?).try_into().unwrap();
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        0x02 => {
+            let octets: [u8; 16] = xor(reader.take(16)?).try_into().unwrap();
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        other => return Err(format!("unknown address family {}", other)),
+    };
+    Ok((ip, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::Value;
+
+    use super::super::{UdpProbe, tests::exchange};
+    use super::*;
+
+    fn message(message_type: u16, attributes: &[u8]) -> Vec<u8> {
+        let mut message = vec![];
+        message.extend_from_slice(&message_type.to_be_bytes());
+        message.extend_from_slice(&(attributes.len() as u16).to_be_bytes());
+        message.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        message.extend_from_slice(TRANSACTION_ID);
+        message.extend_from_slice(attributes);
+        message
+    }
+
+    fn attribute(attribute: u16, value: &[u8]) -> Vec<u8> {
+        let mut out = attribute.to_be_bytes().to_vec();
+        out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        out.extend_from_slice(value);
+        out.resize(out.len() + (4 - value.len() % 4) % 4, 0);
+        out
+    }
+
+    /// XOR-MAPPED-ADDRESS value for 192.0.2.10:54321
+    fn xor_mapped_v4() -> Vec<u8> {
+        let cookie = MAGIC_COOKIE.to_be_bytes();
+        let mut value = vec![0, 0x01];
+        value.extend_from_slice(&(54321 ^ 0x2112u16).to_be_bytes());
+        value.extend([192, 0, 2, 10].iter().zip(cookie).map(|(b, m)| b ^ m));
+        value
+    }
+
+    #[test]
+    fn request_is_a_binding_request() {
+        assert_eq!(request(), message(BINDING_REQUEST, &[]));
+        assert_eq!(request().len(), 20);
+    }
+
+    #[test]
+    fn parses_binding_success_from_stand_in() {
+        let attributes = [
+            attribute(ATTR_SOFTWARE, b"nu-stand-in"),
+            attribute(ATTR_XOR_MAPPED_ADDRESS, &xor_mapped_v4()),
+        ]
+        .concat();
+        let parsed = exchange(UdpProbe::Stun, &message(BINDING_SUCCESS, &attributes)).unwrap();
+        assert_eq!(
+            parsed.get("message_type"),
+            Some(&Value::test_string("binding_success"))
+        );
+        assert_eq!(
+            parsed.get("mapped_address"),
+            Some(&Value::test_string("192.0.2.10"))
+        );
+        assert_eq!(parsed.get("mapped_port"), Some(&Value::test_int(54321)));
+        assert_eq!(
+            parsed.get("software"),
+            Some(&Value::test_string("nu-stand-in"))
+        );
+        assert_eq!(parsed.get("error"), Some(&Value::test_nothing()));
+    }
+
+    #[test]
+    fn parses_ipv6_xor_mapped_address() {
+        let address = "2001:db8::1".parse::<Ipv6Addr>().unwrap().octets();
+        let mask = [&MAGIC_COOKIE.to_be_bytes()[..], TRANSACTION_ID].concat();
+        let mut value = vec![0, 0x02];
+        value.extend_from_slice(&(3478 ^ 0x2112u16).to_be_bytes());
+        value.extend(address.iter().zip(mask).map(|(b, m)| b ^ m));
+        let reply = message(BINDING_SUCCESS, &attribute(ATTR_XOR_MAPPED_ADDRESS, &value));
+        let parsed = parse(&reply).unwrap();
+        assert_eq!(
+            parsed.get("mapped_address"),
+            Some(&Value::test_string("2001:db8::1"))
+        );
+        assert_eq!(parsed.get("mapped_port"), Some(&Value::test_int(3478)));
+    }
+
+    #[test]
+    fn parses_binding_error() {
+        let reply = message(
+            BINDING_ERROR,
+            &attribute(ATTR_ERROR_CODE, b"\0\0\x04\x00Bad Request"),
+        );
+        let parsed = parse(&reply).unwrap();
+        assert_eq!(
+            parsed.get("message_type"),
+            Some(&Value::test_string("binding_error"))
+        );
+        assert_eq!(
+            parsed.get("error"),
+            Some(&Value::test_string("400 Bad Request"))
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_reply() {
+        let reply = message(
+            BINDING_SUCCESS,
+            &attribute(ATTR_XOR_MAPPED_ADDRESS, &xor_mapped_v4()),
+        );
+        for length in [0, 4, 19, 24, reply.len() - 1] {
+            assert_eq!(
+                parse(&reply[..length]),
+                Err("reply is truncated".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_reply() {
+        let mut reply = message(BINDING_SUCCESS, &[]);
+        reply[4..8].copy_from_slice(b"HTTP");
+        assert_eq!(
+            parse(&reply),
+            Err("reply is not a stun message".to_string())
+        );
+
+        let mut value = xor_mapped_v4();
+        value[1] = 0x03;
+        let reply = message(BINDING_SUCCESS, &attribute(ATTR_XOR_MAPPED_ADDRESS, &value));
+        assert_eq!(parse(&reply), Err("unknown address family 3".to_string()));
+    }
+}
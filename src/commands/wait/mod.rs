@@ -0,0 +1,6 @@
+mod plugin;
+#[allow(clippy::module_inception)]
+mod wait;
+mod wait_config;
+pub(crate) use plugin::PortWait;
+pub(super) use wait_config::WaitConfig;
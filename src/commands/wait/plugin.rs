@@ -0,0 +1,94 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, SyntaxShape};
+
+use crate::PortExtension;
+use crate::commands::scan::Reachability;
+
+use super::{WaitConfig, wait::wait};
+
+#[derive(Default)]
+pub struct PortWait;
+
+impl PortWait {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PluginCommand for PortWait {
+    type Plugin = PortExtension;
+
+    fn name(&self) -> &str {
+        "port wait"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("port wait")
+            .required(
+                "host",
+                SyntaxShape::String,
+                "IP address or host name to wait for",
+            )
+            .required("port", SyntaxShape::Int, "port to wait for")
+            .named(
+                "timeout",
+                SyntaxShape::Duration,
+                "give up after waiting this long (default: 60 Seconds)",
+                Some('t'),
+            )
+            .named(
+                "interval",
+                SyntaxShape::Duration,
+                "time between the start of two probes (default: 500 Milliseconds)",
+                Some('i'),
+            )
+            .named(
+                "probe-timeout",
+                SyntaxShape::Duration,
+                "time a single connection attempt may take (default: 1 Second)",
+                None,
+            )
+            .switch(
+                "closed",
+                "wait until the port refuses connections instead, timeouts keep waiting",
+                Some('c'),
+            )
+            .category(Category::Network)
+    }
+
+    fn description(&self) -> &str {
+        "Blocks until a tcp port accepts connections (or stops accepting them), like `wait-for-it`"
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "port wait localhost 5432 --timeout 30sec",
+                description: "wait up to 30 seconds for a local postgres to accept connections",
+                result: None,
+            },
+            Example {
+                example: "docker stop web; port wait 127.0.0.1 8080 --closed",
+                description: "wait until nothing listens on port 8080 anymore",
+                result: None,
+            },
+            Example {
+                example: "try { port wait db 3306 -t 10sec } catch { print 'database is down'; exit 1 }",
+                description: "fail a script when the port does not open in time",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let cfg = WaitConfig::try_from(call)?;
+        let target = Reachability::new(call, engine)?;
+        Ok(PipelineData::Value(wait(target, cfg, call.head)?, None))
+    }
+}
@@ -0,0 +1,113 @@
+use std::time::Instant;
+
+use nu_protocol::{LabeledError, ShellError, Span, Value, record};
+
+use crate::{
+    commands::scan::{Answer, Reachability, Reply},
+    helpers::AsValue,
+};
+
+use super::WaitConfig;
+
+/// Probes the target until it opens (or closes with `--closed`), fails once the timeout passed
+pub(super) fn wait(
+    mut target: Reachability,
+    cfg: WaitConfig,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    let started = Instant::now();
+    let mut attempts: i64 = 0;
+    let mut last: Option<Reply> = None;
+    loop {
+        let remaining = cfg.timeout().saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            return Err(timed_out(&target, &cfg, attempts, last));
+        }
+        target.set_timeout(remaining.min(*cfg.probe_timeout()));
+        let probe_started = Instant::now();
+        let reply = target.probe()?;
+        attempts += 1;
+        if reached(reply.answer, *cfg.closed()) {
+            return Ok(record! {
+                "address" => target.address().as_value(span),
+                "port" => target.port().as_value(span),
+                "state" => match cfg.closed() {
+                    true => "closed",
+                    false => "open",
+                }
+                .as_value(span),
+                "waited" => started.elapsed().as_value(span),
+                "attempts" => attempts.as_value(span),
+            }
+            .as_value(span));
+        }
+        last = Some(reply);
+        let pause = cfg.interval().saturating_sub(probe_started.elapsed());
+        let remaining = cfg.timeout().saturating_sub(started.elapsed());
+        if !target.sleep(pause.min(remaining)) {
+            return Err(ShellError::Interrupted { span }.into());
+        }
+    }
+}
+
+/// Whether `answer` ends the wait. Only a refusal proves a port closed, a timeout or
+/// another error may as well be a filter in front of a listening port.
+fn reached(answer: Answer, closed: bool) -> bool {
+    match closed {
+        true => answer == Answer::Refused,
+        false => answer.is_open(),
+    }
+}
+
+fn timed_out(
+    target: &Reachability,
+    cfg: &WaitConfig,
+    attempts: i64,
+    last: Option<Reply>,
+) -> LabeledError {
+    let state = match cfg.closed() {
+        true => "close",
+        false => "open",
+    };
+    let error = LabeledError::new(format!(
+        "{}:{} did not {} within {:?}",
+        target.address(),
+        target.port(),
+        state,
+        cfg.timeout()
+    ))
+    .with_code("port_wait::timeout")
+    .with_label(
+        format!("gave up after {} attempts", attempts),
+        *cfg.timeout_span(),
+    );
+    match last {
+        Some(Reply {
+            error: Some(error_message),
+            ..
+        }) => error.with_help(format!("last probe: {}", error_message)),
+        Some(Reply { answer, .. }) => error.with_help(format!("last probe: {}", answer)),
+        None => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_is_reached_by_an_open_port_only() {
+        assert!(reached(Answer::Open, false));
+        for answer in [Answer::Refused, Answer::Timeout, Answer::Error] {
+            assert!(!reached(answer, false), "{}", answer);
+        }
+    }
+
+    #[test]
+    fn closed_is_reached_by_a_refusal_only() {
+        assert!(reached(Answer::Refused, true));
+        for answer in [Answer::Open, Answer::Timeout, Answer::Error] {
+            assert!(!reached(answer, true), "{}", answer);
+        }
+    }
+}
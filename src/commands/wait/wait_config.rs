@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use derive_builder::Builder;
+use derive_getters::Getters;
+use nu_plugin::EvaluatedCall;
+use nu_protocol::{LabeledError, Span};
+
+use crate::helpers::FlagHelper;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Builder, Debug, Getters)]
+pub struct WaitConfig {
+    /// Give up once this much time passed
+    timeout: Duration,
+    /// Time between the start of two probes
+    interval: Duration,
+    /// Longest a single probe may take, capped by the time left
+    probe_timeout: Duration,
+    /// Wait for the port to stop accepting connections instead
+    closed: bool,
+    timeout_span: Span,
+}
+
+impl TryFrom<&EvaluatedCall> for WaitConfig {
+    type Error = LabeledError;
+
+    fn try_from(call: &EvaluatedCall) -> Result<Self, Self::Error> {
+        let mut builder = WaitConfigBuilder::create_empty();
+        builder.timeout(duration_flag(call, "timeout", DEFAULT_TIMEOUT)?);
+        builder.interval(duration_flag(call, "interval", DEFAULT_INTERVAL)?);
+        builder.probe_timeout(duration_flag(call, "probe-timeout", DEFAULT_PROBE_TIMEOUT)?);
+        builder.closed(call.has_flag_or("closed", false));
+        builder.timeout_span(call.get_flag_span("timeout").unwrap_or(call.head));
+        builder
+            .build()
+            .map_err(|e| LabeledError::new(e.to_string()))
+    }
+}
+
+/// Reads a positive duration flag
+fn duration_flag(
+    call: &EvaluatedCall,
+    flag: &str,
+    default: Duration,
+) -> Result<Duration, LabeledError> {
+    let Some(value) = call.get_flag_value(flag) else {
+        return Ok(default);
+    };
+    match value.as_duration()? {
+        nanos if nanos > 0 => Ok(Duration::from_nanos(nanos as u64)),
+        _ => Err(LabeledError::new(format!("invalid `--{}`", flag))
+            .with_label("expected a positive duration", value.span())),
+    }
+}
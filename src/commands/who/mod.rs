@@ -0,0 +1,6 @@
+mod plugin;
+#[allow(clippy::module_inception)]
+mod who;
+mod who_query;
+pub(crate) use plugin::PortWho;
+pub(super) use who_query::WhoQuery;
@@ -0,0 +1,86 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, SyntaxShape};
+
+use crate::PortExtension;
+
+use super::{WhoQuery, who::who};
+
+#[derive(Default)]
+pub struct PortWho;
+
+impl PortWho {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PluginCommand for PortWho {
+    type Plugin = PortExtension;
+
+    fn name(&self) -> &str {
+        "port who"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("port who")
+            .optional(
+                "port",
+                SyntaxShape::Int,
+                "local port whose owning processes are returned",
+            )
+            .named(
+                "pid",
+                SyntaxShape::Int,
+                "list the sockets of this process instead",
+                None,
+            )
+            .named(
+                "name",
+                SyntaxShape::String,
+                "list the sockets of the processes with this name instead (case insensitive)",
+                Some('n'),
+            )
+            .category(Category::Network)
+    }
+
+    fn description(&self) -> &str {
+        "Finds the processes owning a local port (tcp or udp, ipv4 or ipv6), or the sockets a process holds"
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "port who 8080",
+                description: "show which process listens on (or is connected from) port 8080",
+                result: None,
+            },
+            Example {
+                example: "port who 8080 | get process_name",
+                description: "only the names of the owning processes",
+                result: None,
+            },
+            Example {
+                example: "port who --name nginx | where state == LISTEN",
+                description: "ports nginx listens on",
+                result: None,
+            },
+            Example {
+                example: "port who --pid $nu.pid",
+                description: "sockets of the current nushell process",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let query = WhoQuery::try_from(call)?;
+        let result = who(query, engine.signals(), call.head)?;
+        Ok(PipelineData::Value(result, None))
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+
+use nu_protocol::{LabeledError, Record, Signals, Span, Value};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate};
+
+use crate::{
+    commands::list::{ConnectionInfo, PortListConfig, collect_connections, processes},
+    helpers::AsValue,
+};
+
+use super::WhoQuery;
+
+pub(super) fn who(query: WhoQuery, signals: &Signals, span: Span) -> Result<Value, LabeledError> {
+    let cfg = PortListConfig::all();
+    let connections = match query {
+        WhoQuery::Port(port) => {
+            let connections = collect_connections(cfg, signals, span, |c| c.local_port == port)?;
+            return Ok(owners(connections, span));
+        }
+        WhoQuery::Pid(pid) => collect_connections(cfg, signals, span, |c| c.pid == pid)?,
+        WhoQuery::Name(name) => {
+            let system = processes(ProcessesToUpdate::All, ProcessRefreshKind::nothing());
+            let pids = system
+                .processes()
+                .iter()
+                .filter(|(_, process)| process.name().to_string_lossy().eq_ignore_ascii_case(&name))
+                .map(|(pid, _)| pid.as_u32())
+                .collect::<HashSet<_>>();
+            collect_connections(cfg, signals, span, |c| pids.contains(&c.pid))?
+        }
+    };
+    let rows = connections
+        .into_iter()
+        .map(|connection| Value::record(connection.into(), span));
+    Ok(Value::list(rows.collect(), span))
+}
+
+/// One record per process, holding its details and the matching sockets
+fn owners(connections: Vec<ConnectionInfo>, span: Span) -> Value {
+    let mut owners: Vec<(u32, Record, Vec<Value>)> = vec![];
+    for mut connection in connections {
+        let index = match owners.iter().position(|(pid, ..)| *pid == connection.pid) {
+            Some(index) => index,
+            None => {
+                let mut owner = Record::new();
+                owner.insert("pid", connection.pid.as_value(span));
+                if let Some(process) = connection.process_info.take() {
+                    process.write_to(&mut owner, span);
+                }
+                owners.push((connection.pid, owner, vec![]));
+                owners.len() - 1
+            }
+        };
+        connection.process_info = None;
+        let mut socket = Record::from(connection);
+        socket.remove("pid");
+        owners[index].2.push(Value::record(socket, span));
+    }
+    let rows = owners.into_iter().map(|(_, mut owner, sockets)| {
+        owner.insert("sockets", Value::list(sockets, span));
+        Value::record(owner, span)
+    });
+    Value::list(rows.collect(), span)
+}
@@ -0,0 +1,45 @@
+use nu_plugin::EvaluatedCall;
+use nu_protocol::LabeledError;
+
+/// What `port who` looks up
+#[derive(Clone, Debug)]
+pub enum WhoQuery {
+    /// Processes owning a socket on this local port
+    Port(u16),
+    /// Sockets held by this process
+    Pid(u32),
+    /// Sockets held by every process with this name
+    Name(String),
+}
+
+impl TryFrom<&EvaluatedCall> for WhoQuery {
+    type Error = LabeledError;
+
+    fn try_from(call: &EvaluatedCall) -> Result<Self, Self::Error> {
+        let port = call.opt::<i64>(0)?;
+        let pid = call.get_flag::<i64>("pid")?;
+        let name = call.get_flag::<String>("name")?;
+        let query = match (port, pid, name) {
+            (Some(port), None, None) => WhoQuery::Port(u16::try_from(port).map_err(|_| {
+                LabeledError::new("invalid port")
+                    .with_label("not a valid port number", call.positional[0].span())
+            })?),
+            (None, Some(pid), None) => WhoQuery::Pid(u32::try_from(pid).map_err(|_| {
+                LabeledError::new("invalid pid").with_label(
+                    "not a valid process id",
+                    call.get_flag_span("pid").unwrap_or(call.head),
+                )
+            })?),
+            (None, None, Some(name)) => WhoQuery::Name(name),
+            (None, None, None) => {
+                return Err(LabeledError::new("nothing to look up")
+                    .with_label("pass a port, `--pid` or `--name`", call.head));
+            }
+            _ => {
+                return Err(LabeledError::new("ambiguous lookup")
+                    .with_label("pass only one of a port, `--pid` or `--name`", call.head));
+            }
+        };
+        Ok(query)
+    }
+}
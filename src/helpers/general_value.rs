@@ -1,7 +1,9 @@
 use std::time::Duration;
 
+use chrono::{DateTime, FixedOffset};
 use nu_protocol::{Record, Span, Value};
 
+#[allow(clippy::wrong_self_convention)]
 pub trait AsValue {
     fn as_value(self, span: Span) -> Value;
 }
@@ -35,6 +37,12 @@ impl AsValue for u32 {
     }
 }
 
+impl AsValue for i64 {
+    fn as_value(self, span: Span) -> Value {
+        Value::int(self, span)
+    }
+}
+
 impl AsValue for String {
     fn as_value(self, span: Span) -> Value {
         Value::string(self, span)
@@ -62,7 +70,7 @@ impl AsValue for bool {
 
 impl AsValue for Duration {
     fn as_value(self, span: Span) -> Value {
-        Value::duration(self.as_nanos().try_into().unwrap_or_else(|_| -1), span)
+        Value::duration(self.as_nanos().try_into().unwrap_or(-1), span)
     }
 }
 
@@ -77,3 +85,9 @@ impl AsValue for Vec<u8> {
         Value::binary(self, span)
     }
 }
+
+impl AsValue for DateTime<FixedOffset> {
+    fn as_value(self, span: Span) -> Value {
+        Value::date(self, span)
+    }
+}
@@ -1,14 +1,27 @@
 mod commands;
 mod helpers;
 
-use commands::{list::PortList, scan::PortScan};
+use commands::{
+    check_bind::PortCheckBind, free::PortFree, kill::PortKill, list::PortList, listen::PortListen,
+    ping::PortPing, scan::PortScan, wait::PortWait, who::PortWho,
+};
 
 use nu_plugin::PluginCommand;
 pub struct PortExtension;
 
 impl nu_plugin::Plugin for PortExtension {
     fn commands(&self) -> Vec<Box<dyn PluginCommand<Plugin = Self>>> {
-        vec![Box::new(PortList::new()), Box::new(PortScan::new())]
+        vec![
+            Box::new(PortList::new()),
+            Box::new(PortScan::new()),
+            Box::new(PortPing::new()),
+            Box::new(PortWait::new()),
+            Box::new(PortWho::new()),
+            Box::new(PortKill::new()),
+            Box::new(PortFree::new()),
+            Box::new(PortCheckBind::new()),
+            Box::new(PortListen::new()),
+        ]
     }
 
     fn version(&self) -> String {
@@ -17,5 +30,5 @@ impl nu_plugin::Plugin for PortExtension {
 }
 
 fn main() {
-    nu_plugin::serve_plugin(&mut PortExtension {}, nu_plugin::MsgPackSerializer {})
+    nu_plugin::serve_plugin(&PortExtension {}, nu_plugin::MsgPackSerializer {})
 }